@@ -0,0 +1,213 @@
+/*
+ * syntaks, a TEI Tak engine
+ * Copyright (c) 2026 Ciekce
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use crate::eval::format_flats;
+use crate::limit::Limits;
+use crate::ptn::{PtnGame, split_games};
+use crate::search::{MAX_DEPTH, MAX_THREADS, SCORE_MATE, SCORE_WIN, Score, Searcher};
+use crate::tei::TeiOptions;
+use crate::ttable::{DEFAULT_TT_SIZE_MIB, MAX_TT_SIZE_MIB};
+use std::fs;
+use std::time::Instant;
+
+const DEFAULT_ANALYSIS_DEPTH: i32 = 10;
+
+struct Config {
+    depth: i32,
+    nodes: Option<usize>,
+}
+
+#[must_use]
+fn format_score(score: Score) -> String {
+    if score > SCORE_WIN {
+        format!("#{}", (SCORE_MATE - score + 1) / 2)
+    } else if score < -SCORE_WIN {
+        format!("#-{}", (SCORE_MATE + score) / 2)
+    } else {
+        format_flats(score)
+    }
+}
+
+fn analyse_game(searcher: &mut Searcher, game: &PtnGame, config: &Config) {
+    searcher.reset();
+
+    let options = TeiOptions::default();
+
+    let mut pos = *game.start_pos();
+    let mut key_history = Vec::with_capacity(game.moves().len());
+
+    println!(
+        "{:>4}  {:<9} {:<9} {:>7}  {:>5}",
+        "Ply", "Played", "Best", "Score", "Depth"
+    );
+
+    for (ply, &mv) in game.moves().iter().enumerate() {
+        let start_time = Instant::now();
+
+        let mut limits = Limits::new(start_time);
+        if let Some(nodes) = config.nodes {
+            limits.set_nodes(nodes);
+        }
+
+        searcher.start_search(
+            &pos,
+            &key_history,
+            start_time,
+            limits,
+            config.depth,
+            &[],
+            &options,
+            false,
+        );
+        searcher.wait();
+
+        let result = searcher.last_result().unwrap();
+
+        println!(
+            "{:>4}  {:<9} {:<9} {:>7}  {:>5}",
+            ply + 1,
+            mv.to_string(),
+            result.mv.to_string(),
+            format_score(result.score * pos.stm().sign()),
+            result.depth
+        );
+
+        key_history.push(pos.key());
+        pos = pos.apply_move(mv);
+    }
+
+    if let Some(result) = game.result() {
+        println!("Result: {}", result);
+    }
+}
+
+// analyse <ptn> [--depth <n>] [--nodes <n>] [--threads <n>] [--hash <mib>]
+// searches the position before every move of every game in the file, scores are P1-relative
+pub fn run(args: &[String]) -> bool {
+    let Some(path) = args.first() else {
+        eprintln!("Missing PTN file");
+        return false;
+    };
+
+    let mut config = Config {
+        depth: DEFAULT_ANALYSIS_DEPTH,
+        nodes: None,
+    };
+
+    let mut threads = 1;
+    let mut hash = DEFAULT_TT_SIZE_MIB;
+
+    let mut i = 1;
+    while i < args.len() {
+        let Some(value) = args.get(i + 1) else {
+            eprintln!("Missing value for '{}'", args[i]);
+            return false;
+        };
+
+        let valid = match args[i].as_str() {
+            "--depth" => value
+                .parse::<i32>()
+                .ok()
+                .filter(|value| (1..=MAX_DEPTH).contains(value))
+                .map(|value| config.depth = value)
+                .is_some(),
+            "--nodes" => value
+                .parse::<usize>()
+                .ok()
+                .filter(|&value| value > 0)
+                .map(|value| config.nodes = Some(value))
+                .is_some(),
+            "--threads" => value
+                .parse::<u32>()
+                .ok()
+                .filter(|value| (1..=MAX_THREADS).contains(value))
+                .map(|value| threads = value)
+                .is_some(),
+            "--hash" => value
+                .parse::<usize>()
+                .ok()
+                .filter(|value| (1..=MAX_TT_SIZE_MIB).contains(value))
+                .map(|value| hash = value)
+                .is_some(),
+            unknown => {
+                eprintln!("Unknown option '{}'", unknown);
+                return false;
+            }
+        };
+
+        if !valid {
+            eprintln!("Invalid value '{}' for '{}'", value, args[i]);
+            return false;
+        }
+
+        i += 2;
+    }
+
+    let ptn = match fs::read_to_string(path) {
+        Ok(ptn) => ptn,
+        Err(err) => {
+            eprintln!("Failed to read PTN file '{}': {}", path, err);
+            return false;
+        }
+    };
+
+    let mut games = Vec::new();
+
+    for (idx, game) in split_games(&ptn).into_iter().enumerate() {
+        match game.parse::<PtnGame>() {
+            Ok(game) => games.push(game),
+            Err(err) => {
+                eprintln!("Failed to parse game {}: {:?}", idx + 1, err);
+                return false;
+            }
+        }
+    }
+
+    if games.is_empty() {
+        eprintln!("No games in '{}'", path);
+        return false;
+    }
+
+    let mut searcher = Searcher::new();
+    searcher.set_quiet(true);
+    searcher.set_threads(threads);
+    searcher.set_tt_size(hash);
+
+    for (idx, game) in games.iter().enumerate() {
+        if idx > 0 {
+            println!();
+        }
+
+        if games.len() > 1 {
+            println!("Game {}/{}", idx + 1, games.len());
+        }
+
+        if let (Some(p1), Some(p2)) = (game.tag("Player1"), game.tag("Player2")) {
+            println!("{} vs {}", p1, p2);
+        }
+
+        analyse_game(&mut searcher, game, &config);
+    }
+
+    true
+}
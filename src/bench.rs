@@ -0,0 +1,137 @@
+/*
+ * syntaks, a TEI Tak engine
+ * Copyright (c) 2026 Ciekce
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use crate::board::Position;
+use crate::limit::Limits;
+use crate::search::Searcher;
+use crate::tei::TeiOptions;
+use std::time::Instant;
+
+pub(crate) const DEFAULT_BENCH_DEPTH: i32 = 8;
+
+// 6x6 positions from engine games, spread over the opening, middlegame and endgame
+const BENCH_TPS: &[&str] = &[
+    "x6/x6/x6/x6/x6/x6 1 1",
+    "x6/x6/x,2,x3,2/x2,1C,x3/x6/x4,1,x 1 3",
+    "x5,1/x2,2,1,x2/x2,1C,x3/x3,2S,x2/x6/x6 2 3",
+    "x6/x,2,1,x3/x,2,2,x3/1,1,2,1,x2/x6/x6 1 5",
+    "x6/x2,1,2,x2/x,1,x4/x3,2C,2,x/x2,21C,x3/x2,1S,x3 2 5",
+    "x3,1,x2/x2,1,1,1S,x/x,2,2,1,x2/x,2,2,1,x2/x,2S,x,2,x2/x6 1 7",
+    "x6/1,1,1,1,1,2S/x,1,x3,1/x,2,2,2,2,x/x6/x5,2 2 7",
+    "x3,1,x2/x2,1,1,x2/x,1,x,1,2,x/2,2,21,2,2,1/x2,2,2,x2/x6 1 9",
+    "x6/x2,1,12S,x2/x,2,2,1,1,x/x,1,2,1,1,1/x2,2,2,x2/2,x4,1 2 9",
+    "x6/1,x,12S,2,x2/x,212,x,2,1,2/2,1,1C,1,1,1/x6/2,x5 1 11",
+    "x6/x,2,2,2,1,1/x,2,2,1,1,1/1,1S,1,1,2,x/x,2,1,2,x2/x,2,2,1,x2 2 11",
+    "x2,2,1,x2/x,1,1,1,1S,x/2,2,2,12C,1,2/2,2,2,1,1,1/x,2S,1,2,1,x/x6 1 13",
+    "x2,1,x3/x,1,2,2,1,x/x,1,1S,2C,1,x/x,1,2,21C,2,1/x,1,1,2,2,2/1S,2,2,2,x2 2 13",
+    "x,1S,x4/2,x2,1,1,1/2,2,1,1,1,x/2,2,21,2,1,x/x,2,2,2,1,x/x3,221S,2,2 1 15",
+    "x2,1,1,x2/1,1,12,1,x2/2,2,2,12C,11S,2/2,2,2,1,1,1/x,2S,1,x,12,x/x6 2 15",
+    "x6/1,1,1,112S,1,1/1,1,2,2C,1,1/2,2,2,221C,2,1/x2,1,2,2,x/x2,2,2,x,2 1 17",
+    "x6/1,1,12,2C,112,1/x,1S,1,1,221,1/x,2S,2,1,1,1/1,x,2,2,2,x/x2,2,2,x2 2 17",
+    "x2,2,x3/1,1,1212C,x,112,1/x,1S,x,1,221,1/x,2S,2,1,1,1/1,x,2,2,2,x/x2,2,2,x2 1 19",
+    "x6/1,1,1,112S,1,1/1,1,2,2C,1,1/2,21,2,221C,2,1/x3,2,2,x/x,2,21,2,2,2 2 19",
+    "x2,2,1,2,2/x,2,21C,1,1,1/1,1S,x,2,1,1/2,x,2,11112S,2,2/x,1,12,2,2,2/x3,1,1,1 1 21",
+    "x6/1,1,1,112S,1,1/1,1,2,2C,1,1/2,21,2,221C,2,1/x2,12,x,2,x/2,2,x,221,2,2 2 21",
+    "x2,2S,2,x2/1,112S,1,2,2,x/2,1,21C,2,1,2/2,12,21,112S,x,1/1,x,112,1,1,1/2,x2,1,x2 1 23",
+    "x6/1,12S,1,12S,1,1/2,1,2,1,112S,1/2,212,12,x,1,1/x,2,21,212,1,2/x3,1,x2 2 23",
+    "x6/x,1,2,2,11212,1/x,1,21C,2,221,1/x,1,2,1,221,1/x,12S,1,2,221S,x/x2,2,2,x2 1 25",
+    "x2,2,x3/x,21,2112C,x,21,2/21,2,1,1,x2/2,2,x,1,1,1/2,x,12,1112S,221,x/2,x,1,x,2,x 2 25",
+    "12S,x,2,x3/x2,1,221S,1,1/1,21,12112C,x,1,1/1,1,2,2,2,2/1,1,21C,2,2,2/1,2,2,221S,2,x 1 27",
+    "x2,2,1,x2/1,1,1112C,1,1,x/2,2,2,2,11112S,21S/2,x,22121,1,1,1/x3,2,12,1/x3,2,x2 2 27",
+    "1S,1S,x4/2,12,2,1,1,1/221S,11,121,x,221,x/21,2,1,x,122,2/2,x,221C,22,1,x/x2,2,2,2,2 1 29",
+    "x,1,1,1,2,x/2,2,2221C,1,2,2/12,221112C,x2,21,x/1,1,2221S,x,2,2/x,2,x,112S,1,1/x2,1,1,x2 2 29",
+];
+
+// searches every position to `depth` on one thread from a cleared state, the total node count
+// only changes if search behaviour does
+pub(crate) fn run_bench(depth: i32) {
+    let mut searcher = Searcher::new();
+    searcher.set_quiet(true);
+
+    let options = TeiOptions::default();
+
+    let mut total_nodes = 0;
+    let mut total_time = 0.0;
+
+    for (idx, tps) in BENCH_TPS.iter().enumerate() {
+        let pos: Position = tps.parse().unwrap();
+
+        searcher.reset();
+
+        let start_time = Instant::now();
+
+        searcher.start_search(
+            &pos,
+            &[],
+            start_time,
+            Limits::new(start_time),
+            depth,
+            &[],
+            &options,
+            false,
+        );
+        searcher.wait();
+
+        total_time += start_time.elapsed().as_secs_f64();
+
+        let result = searcher.last_result().unwrap();
+        total_nodes += result.nodes;
+
+        println!(
+            "Position {}/{}: {} nodes, bestmove {}",
+            idx + 1,
+            BENCH_TPS.len(),
+            result.nodes,
+            result.mv
+        );
+    }
+
+    println!();
+    println!(
+        "{} nodes {} nps",
+        total_nodes,
+        (total_nodes as f64 / total_time) as usize
+    );
+}
+
+// bench [depth]
+pub fn run(args: &[String]) -> bool {
+    let depth = match args {
+        [] => DEFAULT_BENCH_DEPTH,
+        [depth] => match depth.parse::<i32>() {
+            Ok(depth) if depth > 0 => depth,
+            _ => {
+                eprintln!("Invalid depth '{}'", depth);
+                return false;
+            }
+        },
+        [_, extra, ..] => {
+            eprintln!("Unexpected argument '{}'", extra);
+            return false;
+        }
+    };
+
+    run_bench(depth);
+
+    true
+}
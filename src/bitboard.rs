@@ -29,13 +29,56 @@ pub struct Bitboard {
     raw: u64,
 }
 
+const BOARDS: [Bitboard; MAX_SIZE as usize + 1] = {
+    let mut boards = [Bitboard::empty(); MAX_SIZE as usize + 1];
+
+    let mut size = MIN_SIZE;
+    while size <= MAX_SIZE {
+        let rank = (1 << size) - 1;
+
+        let mut raw = 0;
+        let mut rank_idx = 0;
+        while rank_idx < size {
+            raw |= rank << (rank_idx * Square::STRIDE);
+            rank_idx += 1;
+        }
+
+        boards[size as usize] = Bitboard::from_raw(raw);
+        size += 1;
+    }
+
+    boards
+};
+
+const BOARD_EDGES: [[Bitboard; Direction::COUNT]; MAX_SIZE as usize + 1] = {
+    let mut edges = [[Bitboard::empty(); Direction::COUNT]; MAX_SIZE as usize + 1];
+
+    let mut size = MIN_SIZE;
+    while size <= MAX_SIZE {
+        let board = BOARDS[size as usize];
+        let last = size - 1;
+
+        edges[size as usize] = [
+            Bitboard::LOWER_EDGE.shl(last * Square::STRIDE).and(board),
+            Bitboard::LOWER_EDGE.and(board),
+            Bitboard::LEFT_EDGE.and(board),
+            Bitboard::LEFT_EDGE.shl(last).and(board),
+        ];
+
+        size += 1;
+    }
+
+    edges
+};
+
 impl Bitboard {
-    const MASK: u64 = (1 << Square::COUNT) - 1;
+    const MASK: u64 = u64::MAX >> (64 - Square::COUNT);
 
-    pub const UPPER_EDGE: Self = Self::from_raw(0xfc0000000);
-    pub const LOWER_EDGE: Self = Self::from_raw(0x3f);
-    pub const LEFT_EDGE: Self = Self::from_raw(0x41041041);
-    pub const RIGHT_EDGE: Self = Self::from_raw(0x820820820);
+    // edges of the full 8x8 square grid, see `board_edge` for the edges of a particular board size
+    pub const UPPER_EDGE: Self = Self::from_raw(0xff00000000000000);
+    pub const LOWER_EDGE: Self = Self::from_raw(0xff);
+    pub const LEFT_EDGE: Self = Self::from_raw(0x0101010101010101);
+    pub const RIGHT_EDGE: Self = Self::from_raw(0x8080808080808080);
 
     #[must_use]
     pub const fn empty() -> Self {
@@ -52,6 +95,18 @@ impl Bitboard {
         [Self::UPPER_EDGE, Self::LOWER_EDGE, Self::LEFT_EDGE, Self::RIGHT_EDGE][dir.idx()]
     }
 
+    #[must_use]
+    pub const fn board(size: u32) -> Self {
+        debug_assert!(size >= MIN_SIZE && size <= MAX_SIZE);
+        BOARDS[size as usize]
+    }
+
+    #[must_use]
+    pub const fn board_edge(size: u32, dir: Direction) -> Self {
+        debug_assert!(size >= MIN_SIZE && size <= MAX_SIZE);
+        BOARD_EDGES[size as usize][dir.idx()]
+    }
+
     #[must_use]
     pub const fn raw(self) -> u64 {
         self.raw
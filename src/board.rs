@@ -25,8 +25,14 @@ use crate::bitboard::Bitboard;
 use crate::core::*;
 use crate::hits::find_hit_for_dir;
 use crate::keys;
-use crate::road::has_road;
+use crate::movegen::generate_spread_moves;
+use crate::road::{Road, find_road, has_road, placement_threats};
+use crate::symmetry::Symmetry;
 use crate::takmove::Move;
+use crate::util::bits::{BitReader, BitWriter};
+use crate::util::checksum::Checksum;
+use crate::util::le::read_u16;
+use arrayvec::ArrayVec;
 use std::cmp::Ordering;
 use std::str::FromStr;
 
@@ -69,15 +75,15 @@ impl Keys {
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub struct Stacks {
-    players: [u64; Square::COUNT],
+    players: [u128; Square::COUNT],
     heights: [u8; Square::COUNT],
     tops: [Option<PieceType>; Square::COUNT],
     keys: Keys,
 }
 
 impl Stacks {
-    // all flats + cap
-    pub const MAX_HEIGHT: usize = 30 + 30 + 1;
+    // all flats + caps on 8x8
+    pub const MAX_HEIGHT: usize = 50 + 50 + 2;
 
     #[must_use]
     pub fn is_empty(&self, sq: Square) -> bool {
@@ -104,7 +110,7 @@ impl Stacks {
     }
 
     #[must_use]
-    pub fn players(&self, sq: Square) -> u64 {
+    pub fn players(&self, sq: Square) -> u128 {
         self.players[sq.idx()]
     }
 
@@ -120,7 +126,7 @@ impl Stacks {
         let height = self.heights[sq.idx()];
         self.keys.toggle_player_key(height, player, sq);
 
-        self.players[sq.idx()] |= (player.raw() as u64) << self.heights[sq.idx()];
+        self.players[sq.idx()] |= (player.raw() as u128) << self.heights[sq.idx()];
         self.heights[sq.idx()] += 1;
         self.tops[sq.idx()] = Some(pt);
     }
@@ -128,7 +134,7 @@ impl Stacks {
     fn take(&mut self, sq: Square, count: u8) -> (u8, PieceType, Option<Player>) {
         debug_assert!(count <= self.heights[sq.idx()]);
         debug_assert!(count > 0);
-        debug_assert!(count <= 6);
+        debug_assert!(count <= Move::MAX_CARRY);
 
         let players = (self.players[sq.idx()] >> (self.heights[sq.idx()] - count)) & ((1 << count) - 1);
         let top = self.tops[sq.idx()].unwrap();
@@ -157,6 +163,15 @@ impl Stacks {
         }
     }
 
+    fn replace_top(&mut self, sq: Square, pt: PieceType) {
+        let prev_top = self.tops[sq.idx()].unwrap();
+
+        self.keys.toggle_top_key(prev_top, sq);
+        self.keys.toggle_top_key(pt, sq);
+
+        self.tops[sq.idx()] = Some(pt);
+    }
+
     fn regen_key(&mut self, occ: Bitboard) {
         self.keys.reset();
 
@@ -186,7 +201,7 @@ impl Stacks {
 impl Default for Stacks {
     fn default() -> Self {
         Self {
-            players: [u64::default(); Square::COUNT],
+            players: [u128::default(); Square::COUNT],
             heights: [u8::default(); Square::COUNT],
             tops: [None; Square::COUNT],
             keys: Default::default(),
@@ -195,7 +210,7 @@ impl Default for Stacks {
 }
 
 pub struct StackIterator {
-    players: u64,
+    players: u128,
     height: u8,
     idx: u8,
 }
@@ -214,14 +229,51 @@ impl Iterator for StackIterator {
     }
 }
 
+// everything `Position::unmake_move` cannot cheaply recompute
+#[derive(Copy, Clone, Debug)]
+pub struct Undo {
+    players: [Bitboard; Player::COUNT],
+    pieces: [Bitboard; PieceType::COUNT],
+    crushed: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct RoadThreats {
+    // empty squares where a flat or capstone placement completes a road
+    pub placements: Bitboard,
+    pub spreads: Vec<Move>,
+}
+
+impl RoadThreats {
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.placements.is_empty() && self.spreads.is_empty()
+    }
+}
+
 pub enum FlatCountOutcome {
     None,
     Draw,
     Win(Player),
 }
 
+// (flats, caps) per player, indexed by board size
+const STARTING_RESERVES: [(u8, u8); MAX_SIZE as usize + 1] = [
+    (0, 0),
+    (0, 0),
+    (0, 0),
+    (10, 0),
+    (15, 0),
+    (21, 1),
+    (30, 1),
+    (40, 2),
+    (50, 2),
+];
+
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub struct Position {
+    size: u8,
+    half_komi: u8,
     stacks: Stacks,
     players: [Bitboard; Player::COUNT],
     pieces: [Bitboard; PieceType::COUNT],
@@ -233,17 +285,25 @@ pub struct Position {
 }
 
 impl Position {
-    pub const CARRY_LIMIT: u8 = 6;
-    pub const KOMI: u32 = 2;
+    pub const DEFAULT_SIZE: u32 = 6;
+
+    pub const DEFAULT_HALF_KOMI: u32 = 4;
+    pub const MAX_HALF_KOMI: u32 = 16;
 
     #[must_use]
-    pub fn startpos() -> Self {
+    pub fn startpos(size: u32) -> Self {
+        assert!((MIN_SIZE..=MAX_SIZE).contains(&size));
+
+        let (flats, caps) = STARTING_RESERVES[size as usize];
+
         Self {
+            size: size as u8,
+            half_komi: Self::DEFAULT_HALF_KOMI as u8,
             stacks: Stacks::default(),
             players: [Bitboard::empty(); Player::COUNT],
             pieces: [Bitboard::empty(); PieceType::COUNT],
-            flats_in_hand: [30; Player::COUNT],
-            caps_in_hand: [1; Player::COUNT],
+            flats_in_hand: [flats; Player::COUNT],
+            caps_in_hand: [caps; Player::COUNT],
             stm: Player::P1,
             ply: 0,
             player_key: 0,
@@ -256,17 +316,22 @@ impl Position {
         }
 
         let ranks: Vec<&str> = parts[0].split('/').collect();
-        if ranks.len() != 6 {
+        let size = ranks.len() as u32;
+
+        if !(MIN_SIZE..=MAX_SIZE).contains(&size) {
             return Err(TpsError::WrongNumberOfRanks);
         }
 
-        let mut pos = Self::startpos();
+        let mut pos = Self::startpos(size);
+
+        let mut used_flats = [0; Player::COUNT];
+        let mut used_caps = [0; Player::COUNT];
 
-        for rank_idx in 0..6 {
+        for rank_idx in 0..size {
             let mut file_idx = 0;
 
-            for stack in ranks[5 - rank_idx as usize].split(',') {
-                if file_idx >= 6 {
+            for stack in ranks[(size - 1 - rank_idx) as usize].split(',') {
+                if file_idx >= size {
                     return Err(TpsError::WrongNumberOfFiles);
                 }
 
@@ -317,15 +382,31 @@ impl Position {
                         }
                     }
 
+                    for (idx, &player) in players.iter().enumerate() {
+                        if idx == players.len() - 1 && top == PieceType::Capstone {
+                            used_caps[player.idx()] += 1;
+                        } else {
+                            used_flats[player.idx()] += 1;
+                        }
+                    }
+
                     file_idx += 1;
                 }
             }
 
-            if file_idx > 6 {
+            if file_idx != size {
                 return Err(TpsError::WrongNumberOfFiles);
             }
         }
 
+        for player in [Player::P1, Player::P2] {
+            if used_flats[player.idx()] > pos.flats_in_hand(player)
+                || used_caps[player.idx()] > pos.caps_in_hand(player)
+            {
+                return Err(TpsError::TooManyPieces);
+            }
+        }
+
         match parts[1] {
             "1" => pos.stm = Player::P1,
             "2" => pos.stm = Player::P2,
@@ -344,6 +425,32 @@ impl Position {
         Ok(pos)
     }
 
+    #[must_use]
+    pub fn size(&self) -> u32 {
+        self.size as u32
+    }
+
+    #[must_use]
+    pub fn half_komi(&self) -> u32 {
+        self.half_komi as u32
+    }
+
+    // komi is not part of the TPS, so it has to be set separately for each game
+    pub fn set_half_komi(&mut self, half_komi: u32) {
+        assert!(half_komi <= Self::MAX_HALF_KOMI);
+        self.half_komi = half_komi as u8;
+    }
+
+    #[must_use]
+    pub fn carry_limit(&self) -> u8 {
+        self.size
+    }
+
+    #[must_use]
+    pub fn board(&self) -> Bitboard {
+        Bitboard::board(self.size())
+    }
+
     #[must_use]
     pub fn stm(&self) -> Player {
         self.stm
@@ -374,6 +481,11 @@ impl Position {
         self.players[0] | self.players[1]
     }
 
+    #[must_use]
+    pub fn empty_squares(&self) -> Bitboard {
+        self.board() & !self.occ()
+    }
+
     #[must_use]
     pub fn flats_in_hand(&self, player: Player) -> u8 {
         self.flats_in_hand[player.idx()]
@@ -424,11 +536,6 @@ impl Position {
         self.piece_bb(PieceType::Wall) | self.piece_bb(PieceType::Capstone)
     }
 
-    #[must_use]
-    pub fn blockers(&self, player: Player) -> Bitboard {
-        self.all_blockers() & self.player_bb(player)
-    }
-
     #[must_use]
     pub fn all_roads(&self) -> Bitboard {
         self.piece_bb(PieceType::Flat) | self.piece_bb(PieceType::Capstone)
@@ -441,7 +548,48 @@ impl Position {
 
     #[must_use]
     pub fn has_road(&self, player: Player) -> bool {
-        has_road(self.roads(player))
+        has_road(self.roads(player), self.size())
+    }
+
+    #[must_use]
+    pub fn road(&self, player: Player) -> Option<Road> {
+        find_road(self.roads(player), self.size())
+    }
+
+    // empty squares where a flat or capstone placement would immediately win by road
+    // for `player`, if it were their turn
+    #[must_use]
+    pub fn placement_road_threats(&self, player: Player) -> Bitboard {
+        // the opening moves place the opponent's pieces, and no road can exist yet anyway
+        if self.ply() < 2 || self.has_no_more_pieces(player) {
+            return Bitboard::empty();
+        }
+
+        placement_threats(self.roads(player), self.empty_squares(), self.size())
+    }
+
+    // moves that would immediately win by road for `player`, if it were their turn
+    #[must_use]
+    pub fn road_threats(&self, player: Player) -> RoadThreats {
+        let mut threats = RoadThreats {
+            placements: self.placement_road_threats(player),
+            spreads: Vec::with_capacity(256),
+        };
+
+        if self.ply() < 2 {
+            return threats;
+        }
+
+        let pos = if self.stm() == player {
+            *self
+        } else {
+            self.apply_nullmove()
+        };
+
+        generate_spread_moves(&mut threats.spreads, &pos);
+        threats.spreads.retain(|&mv| pos.apply_move(mv).has_road(player));
+
+        threats
     }
 
     #[must_use]
@@ -449,22 +597,27 @@ impl Position {
         self.flats_in_hand(player) == 0 && self.caps_in_hand(player) == 0
     }
 
+    // in whole flats, rounded down so that a half-flat deficit still counts as losing
     #[must_use]
     pub fn fcd(&self, player: Player) -> i32 {
-        let p1_advantage = self.player_piece_bb(Piece::P1Flat).popcount() as i32
-            - self.player_piece_bb(Piece::P2Flat).popcount() as i32
-            - Self::KOMI as i32;
-        p1_advantage * player.sign()
+        let p1_half_advantage = 2 * self.player_piece_bb(Piece::P1Flat).popcount() as i32
+            - 2 * self.player_piece_bb(Piece::P2Flat).popcount() as i32
+            - self.half_komi() as i32;
+        (p1_half_advantage * player.sign()).div_euclid(2)
     }
 
     #[must_use]
     pub fn count_flats(&self) -> FlatCountOutcome {
-        if !(!self.occ()).is_empty() && !self.has_no_more_pieces(Player::P1) && !self.has_no_more_pieces(Player::P2) {
+        if !self.empty_squares().is_empty()
+            && !self.has_no_more_pieces(Player::P1)
+            && !self.has_no_more_pieces(Player::P2)
+        {
             return FlatCountOutcome::None;
         }
 
-        let p1_flats = self.player_piece_bb(Piece::P1Flat).popcount();
-        let p2_flats = self.player_piece_bb(Piece::P2Flat).popcount() + Self::KOMI;
+        // doubled, so that half-point komi can be counted exactly
+        let p1_flats = 2 * self.player_piece_bb(Piece::P1Flat).popcount();
+        let p2_flats = 2 * self.player_piece_bb(Piece::P2Flat).popcount() + self.half_komi();
 
         match p1_flats.cmp(&p2_flats) {
             Ordering::Less => FlatCountOutcome::Win(Player::P2),
@@ -486,13 +639,13 @@ impl Position {
 
             let pattern = mv.pattern();
 
-            let taken = 6 - pattern.trailing_zeros();
-            if taken > self.stacks.height(mv.sq()) as u32 {
+            let taken = mv.taken();
+            if taken > self.carry_limit() || taken > self.stacks.height(mv.sq()) {
                 return false;
             }
 
             let dist = pattern.count_ones() as u8;
-            let (max_dist, hit_sq) = find_hit_for_dir(self.all_blockers(), mv.sq(), mv.dir());
+            let (max_dist, hit_sq) = find_hit_for_dir(self.all_blockers(), mv.sq(), mv.dir(), self.size());
 
             if dist > max_dist {
                 return false;
@@ -503,7 +656,7 @@ impl Position {
                     PieceType::Flat => {}
                     PieceType::Wall => {
                         // multiple pieces dropped on the final square
-                        if pattern & (1 << (Self::CARRY_LIMIT - 1)) == 0 {
+                        if pattern & (1 << (Move::MAX_CARRY - 1)) == 0 {
                             return false;
                         }
 
@@ -520,7 +673,7 @@ impl Position {
                 return false;
             }
 
-            if !self.stacks.is_empty(mv.sq()) {
+            if !self.empty_squares().has_sq(mv.sq()) {
                 return false;
             }
 
@@ -540,6 +693,16 @@ impl Position {
     #[must_use]
     pub fn apply_move(&self, mv: Move) -> Self {
         let mut new_pos = *self;
+        new_pos.make_move(mv);
+        new_pos
+    }
+
+    pub fn make_move(&mut self, mv: Move) -> Undo {
+        let undo = Undo {
+            players: self.players,
+            pieces: self.pieces,
+            crushed: mv.is_spread() && self.stacks.top(mv.spread_dest()) == Some(PieceType::Wall),
+        };
 
         if mv.is_spread() {
             debug_assert_ne!(self.stacks.top(mv.sq()), None);
@@ -550,10 +713,12 @@ impl Position {
             let dir = mv.dir();
 
             let dropped = pattern.trailing_zeros();
-            let taken = 6 - dropped;
+            let taken = mv.taken();
+
+            let prev_dst_top = self.stacks.top(mv.spread_dest());
 
             let mut pattern = pattern >> dropped;
-            let (mut players, top, new_top_player) = new_pos.stacks.take(mv.sq(), taken as u8);
+            let (mut players, top, new_top_player) = self.stacks.take(mv.sq(), taken);
 
             let mut new_flats_bb = Bitboard::empty();
             let mut new_player_bbs = [Bitboard::empty(); Player::COUNT];
@@ -562,11 +727,11 @@ impl Position {
                 new_player_bbs[new_top_player.idx()].set_sq(mv.sq());
                 new_flats_bb.set_sq(mv.sq());
             } else {
-                new_pos.players[self.stm().idx()].toggle_sq(mv.sq());
+                self.players[self.stm().idx()].toggle_sq(mv.sq());
             }
 
             if top != PieceType::Flat || new_top_player.is_none() {
-                new_pos.pieces[top.idx()].toggle_sq(mv.sq());
+                self.pieces[top.idx()].toggle_sq(mv.sq());
             }
 
             let mut sq = mv.sq().shift(dir).unwrap();
@@ -575,7 +740,7 @@ impl Position {
                 let player = Player::from_raw(players & 0x1).unwrap();
                 let pt = if idx == taken - 1 { top } else { PieceType::Flat };
 
-                new_pos.stacks.push(sq, pt, player);
+                self.stacks.push(sq, pt, player);
 
                 pattern >>= 1;
                 players >>= 1;
@@ -593,37 +758,36 @@ impl Position {
             debug_assert_eq!(new_player_bbs[0] & new_player_bbs[1], Bitboard::empty());
 
             for player in 0..Player::COUNT {
-                new_pos.players[player] =
-                    (new_pos.players[player] | new_player_bbs[player]) & !new_player_bbs[player ^ 0x1];
+                self.players[player] = (self.players[player] | new_player_bbs[player]) & !new_player_bbs[player ^ 0x1];
             }
 
-            new_pos.pieces[top.idx()].set_sq(sq);
-            new_pos.pieces[PieceType::Flat.idx()] |= new_flats_bb;
+            self.pieces[top.idx()].set_sq(sq);
+            self.pieces[PieceType::Flat.idx()] |= new_flats_bb;
 
-            if let Some(prev_dst_top) = self.stacks.top(sq)
+            if let Some(prev_dst_top) = prev_dst_top
                 && prev_dst_top != top
             {
-                new_pos.pieces[prev_dst_top.idx()].clear_sq(sq);
-                new_pos.pieces[top.idx()].set_sq(sq);
+                self.pieces[prev_dst_top.idx()].clear_sq(sq);
+                self.pieces[top.idx()].set_sq(sq);
             }
 
             debug_assert_eq!(
-                new_pos.pieces[PieceType::Flat.idx()]
-                    & new_pos.pieces[PieceType::Wall.idx()]
-                    & new_pos.pieces[PieceType::Capstone.idx()],
+                self.pieces[PieceType::Flat.idx()]
+                    & self.pieces[PieceType::Wall.idx()]
+                    & self.pieces[PieceType::Capstone.idx()],
                 Bitboard::empty()
             );
 
             debug_assert_eq!(
-                new_pos.players[Player::P1.idx()] & new_pos.players[Player::P2.idx()],
+                self.players[Player::P1.idx()] & self.players[Player::P2.idx()],
                 Bitboard::empty()
             );
 
             debug_assert_eq!(
-                new_pos.pieces[PieceType::Flat.idx()]
-                    | new_pos.pieces[PieceType::Wall.idx()]
-                    | new_pos.pieces[PieceType::Capstone.idx()],
-                new_pos.players[Player::P1.idx()] | new_pos.players[Player::P2.idx()]
+                self.pieces[PieceType::Flat.idx()]
+                    | self.pieces[PieceType::Wall.idx()]
+                    | self.pieces[PieceType::Capstone.idx()],
+                self.players[Player::P1.idx()] | self.players[Player::P2.idx()]
             );
         } else {
             debug_assert_eq!(self.stacks.top(mv.sq()), None);
@@ -631,30 +795,106 @@ impl Position {
 
             let dropped_player = if self.ply() < 2 { self.stm().flip() } else { self.stm() };
 
-            new_pos.stacks.push(mv.sq(), mv.pt(), dropped_player);
+            self.stacks.push(mv.sq(), mv.pt(), dropped_player);
 
-            new_pos.players[dropped_player.idx()].set_sq(mv.sq());
-            new_pos.pieces[mv.pt().idx()].set_sq(mv.sq());
+            self.players[dropped_player.idx()].set_sq(mv.sq());
+            self.pieces[mv.pt().idx()].set_sq(mv.sq());
 
             match mv.pt() {
-                PieceType::Capstone => new_pos.caps_in_hand[dropped_player.idx()] -= 1,
-                _ => new_pos.flats_in_hand[dropped_player.idx()] -= 1,
+                PieceType::Capstone => self.caps_in_hand[dropped_player.idx()] -= 1,
+                _ => self.flats_in_hand[dropped_player.idx()] -= 1,
             }
         }
 
-        new_pos.stm = new_pos.stm.flip();
-        new_pos.ply += 1;
+        self.stm = self.stm.flip();
+        self.ply += 1;
 
-        new_pos.player_key ^= keys::p2_key();
+        self.player_key ^= keys::p2_key();
 
         #[cfg(debug_assertions)]
-        {
-            let mut other_new = new_pos;
-            other_new.regen();
-            assert_eq!(new_pos, other_new);
+        self.verify();
+
+        undo
+    }
+
+    // `mv` must be the move passed to the `make_move` call that returned `undo`
+    pub fn unmake_move(&mut self, mv: Move, undo: &Undo) {
+        self.stm = self.stm.flip();
+        self.ply -= 1;
+
+        self.player_key ^= keys::p2_key();
+
+        if mv.is_spread() {
+            let dir = mv.dir();
+            let taken = mv.taken();
+
+            // replay the spread to find how many pieces were dropped on each square
+            let mut drops = ArrayVec::<(Square, u8), { Move::MAX_CARRY as usize }>::new();
+
+            let mut pattern = mv.pattern() >> mv.pattern().trailing_zeros();
+            let mut sq = mv.sq().shift(dir).unwrap();
+            let mut count = 0;
+
+            for _ in 0..taken {
+                count += 1;
+                pattern >>= 1;
+
+                if (pattern & 0x1) != 0 {
+                    drops.push((sq, count));
+                    sq = sq.shift(dir).unwrap();
+                    count = 0;
+                }
+            }
+
+            drops.push((sq, count));
+
+            // pick the pieces back up, last square first
+            let mut players = 0u16;
+            let mut top = PieceType::Flat;
+
+            for (idx, &(sq, count)) in drops.iter().enumerate().rev() {
+                let (dropped_players, dropped_top, _) = self.stacks.take(sq, count);
+
+                if idx == drops.len() - 1 {
+                    top = dropped_top;
+                }
+
+                players = (players << count) | dropped_players as u16;
+            }
+
+            if undo.crushed {
+                self.stacks.replace_top(sq, PieceType::Wall);
+            }
+
+            for idx in 0..taken {
+                let player = Player::from_raw(((players >> idx) & 0x1) as u8).unwrap();
+                let pt = if idx == taken - 1 { top } else { PieceType::Flat };
+
+                self.stacks.push(mv.sq(), pt, player);
+            }
+        } else {
+            let dropped_player = if self.ply() < 2 { self.stm().flip() } else { self.stm() };
+
+            self.stacks.take(mv.sq(), 1);
+
+            match mv.pt() {
+                PieceType::Capstone => self.caps_in_hand[dropped_player.idx()] += 1,
+                _ => self.flats_in_hand[dropped_player.idx()] += 1,
+            }
         }
 
-        new_pos
+        self.players = undo.players;
+        self.pieces = undo.pieces;
+
+        #[cfg(debug_assertions)]
+        self.verify();
+    }
+
+    #[cfg(debug_assertions)]
+    fn verify(&self) {
+        let mut regenerated = *self;
+        regenerated.regen();
+        assert_eq!(*self, regenerated);
     }
 
     #[must_use]
@@ -673,17 +913,19 @@ impl Position {
     pub fn tps(&self) -> String {
         let mut tps = String::with_capacity(21);
 
-        for rank in (0..6).rev() {
+        for rank in (0..self.size()).rev() {
             let mut groups = Vec::new();
 
             let mut file = 0;
-            while file < 6 {
+            while file < self.size() {
                 let sq = Square::from_file_rank(file, rank).unwrap();
 
                 if self.stacks.is_empty(sq) {
                     let mut empty = 1;
 
-                    while file < 5 && self.stacks.is_empty(Square::from_file_rank(file + 1, rank).unwrap()) {
+                    while file < self.size() - 1
+                        && self.stacks.is_empty(Square::from_file_rank(file + 1, rank).unwrap())
+                    {
                         file += 1;
                         empty += 1;
                     }
@@ -694,22 +936,7 @@ impl Position {
                         groups.push("x".to_owned());
                     }
                 } else {
-                    let mut stack_str = String::with_capacity(self.stacks.height(sq) as usize + 1);
-
-                    for player in self.stacks.iter(sq) {
-                        match player {
-                            Player::P1 => stack_str.push('1'),
-                            Player::P2 => stack_str.push('2'),
-                        }
-                    }
-
-                    match self.stacks.top(sq).unwrap() {
-                        PieceType::Flat => {}
-                        PieceType::Wall => stack_str.push('S'),
-                        PieceType::Capstone => stack_str.push('C'),
-                    }
-
-                    groups.push(stack_str);
+                    groups.push(self.stack_tps(sq));
                 }
 
                 file += 1;
@@ -732,12 +959,284 @@ impl Position {
         tps
     }
 
+    // a single stack in TPS notation, bottom to top. empty squares are "x"
+    #[must_use]
+    pub fn stack_tps(&self, sq: Square) -> String {
+        let Some(top) = self.stacks.top(sq) else {
+            return "x".to_owned();
+        };
+
+        let mut stack_str = String::with_capacity(self.stacks.height(sq) as usize + 1);
+
+        for player in self.stacks.iter(sq) {
+            match player {
+                Player::P1 => stack_str.push('1'),
+                Player::P2 => stack_str.push('2'),
+            }
+        }
+
+        match top {
+            PieceType::Flat => {}
+            PieceType::Wall => stack_str.push('S'),
+            PieceType::Capstone => stack_str.push('C'),
+        }
+
+        stack_str
+    }
+
+    // a grid with each square's owner and top piece, followed by the height of taller stacks.
+    // with `color`, pieces are highlighted by owner using ANSI escapes
+    #[must_use]
+    pub fn render(&self, color: bool) -> String {
+        const CELL_WIDTH: usize = 5;
+
+        let size = self.size() as usize;
+
+        let mut files = String::from("  ");
+        for file in 0..size {
+            files.push_str(&format!(" {:^CELL_WIDTH$}", (b'a' + file as u8) as char));
+        }
+        let files = format!("{}\n", files.trim_end());
+
+        let separator = format!("  +{}\n", format!("{}+", "-".repeat(CELL_WIDTH)).repeat(size));
+
+        let mut result = files.clone();
+
+        for rank in (0..self.size()).rev() {
+            result.push_str(&separator);
+            result.push_str(&format!("{} |", rank + 1));
+
+            for file in 0..self.size() {
+                let sq = Square::from_file_rank(file, rank).unwrap();
+
+                match (self.stacks.top(sq), self.stacks.top_player(sq)) {
+                    (Some(top), Some(player)) => {
+                        let height = self.stacks.height(sq);
+
+                        let mut cell = format!("{}{}", player.raw() + 1, top);
+                        if height > 1 {
+                            cell.push_str(&height.to_string());
+                        }
+
+                        let cell = format!("{:^CELL_WIDTH$}", cell);
+
+                        if color {
+                            let code = match player {
+                                Player::P1 => "1;33",
+                                Player::P2 => "1;36",
+                            };
+                            result.push_str(&format!("\x1b[{}m{}\x1b[0m", code, cell));
+                        } else {
+                            result.push_str(&cell);
+                        }
+                    }
+                    _ => result.push_str(&" ".repeat(CELL_WIDTH)),
+                }
+
+                result.push('|');
+            }
+
+            result.push_str(&format!(" {}\n", rank + 1));
+        }
+
+        result.push_str(&separator);
+        result.push_str(&files);
+
+        result
+    }
+
+    // the same position with the board rotated or mirrored
+    #[must_use]
+    pub fn transform(&self, sym: Symmetry) -> Self {
+        let mut pos = Self::startpos(self.size());
+
+        pos.half_komi = self.half_komi;
+        pos.stm = self.stm;
+        pos.ply = self.ply;
+
+        for sq in self.occ() {
+            let dst = sym.apply_square(sq, self.size());
+            let height = self.stacks.height(sq);
+
+            for (idx, player) in self.stacks.iter(sq).enumerate() {
+                let pt = if idx + 1 == height as usize {
+                    self.stacks.top(sq).unwrap()
+                } else {
+                    PieceType::Flat
+                };
+                pos.stacks.push(dst, pt, player);
+            }
+        }
+
+        pos.regen();
+
+        pos
+    }
+
+    // a variable-length bitstream followed by a 16-bit checksum:
+    //   size - 3 (3 bits), stm (1), half komi (5), ply (16),
+    //   flats (6) and caps (2) in hand for each player,
+    //   occupancy (1 per square, a1 first and rank by rank),
+    //   then for each occupied square its top piece (2), height and owners from the bottom up.
+    //   heights up to 7 take 3 bits, anything taller is 7 followed by the height in 7 bits
+    pub fn pack(&self, dst: &mut Vec<u8>) {
+        let start = dst.len();
+
+        let mut writer = BitWriter::new(dst);
+
+        writer.write((self.size() - MIN_SIZE) as u64, 3);
+        writer.write(self.stm.raw() as u64, 1);
+        writer.write(self.half_komi as u64, 5);
+        writer.write(self.ply as u64, 16);
+
+        for player in [Player::P1, Player::P2] {
+            writer.write(self.flats_in_hand(player) as u64, 6);
+            writer.write(self.caps_in_hand(player) as u64, 2);
+        }
+
+        let squares = || {
+            (0..self.size())
+                .flat_map(|rank| (0..self.size()).map(move |file| Square::from_file_rank(file, rank).unwrap()))
+        };
+
+        for sq in squares() {
+            writer.write(!self.stacks.is_empty(sq) as u64, 1);
+        }
+
+        for sq in squares().filter(|&sq| !self.stacks.is_empty(sq)) {
+            let height = self.stacks.height(sq) as u32;
+            let players = self.stacks.players(sq);
+
+            writer.write(self.stacks.top(sq).unwrap().raw() as u64, 2);
+
+            if height < PACKED_HEIGHT_ESCAPE {
+                writer.write(height as u64, 3);
+            } else {
+                writer.write(PACKED_HEIGHT_ESCAPE as u64, 3);
+                writer.write(height as u64, 7);
+            }
+
+            writer.write(players as u64, height.min(64));
+            if height > 64 {
+                writer.write((players >> 64) as u64, height - 64);
+            }
+        }
+
+        let checksum = packed_checksum(&dst[start..]);
+        dst.extend_from_slice(&checksum.to_le_bytes());
+    }
+
+    // returns the position and the number of bytes it took up
+    pub fn unpack(src: &[u8]) -> Result<(Self, usize), UnpackError> {
+        let mut reader = BitReader::new(src);
+        let mut read = |bits| reader.read(bits).ok_or(UnpackError::Truncated);
+
+        let size = read(3)? as u32 + MIN_SIZE;
+        if size > MAX_SIZE {
+            return Err(UnpackError::InvalidSize);
+        }
+
+        let mut pos = Self::startpos(size);
+
+        pos.stm = Player::from_raw(read(1)? as u8).unwrap();
+
+        let half_komi = read(5)? as u32;
+        if half_komi > Self::MAX_HALF_KOMI {
+            return Err(UnpackError::InvalidHalfKomi);
+        }
+        pos.half_komi = half_komi as u8;
+
+        pos.ply = read(16)? as u16;
+
+        let mut flats_in_hand = [0; Player::COUNT];
+        let mut caps_in_hand = [0; Player::COUNT];
+
+        for player in [Player::P1, Player::P2] {
+            flats_in_hand[player.idx()] = read(6)? as u8;
+            caps_in_hand[player.idx()] = read(2)? as u8;
+        }
+
+        let mut occ = Bitboard::empty();
+
+        for rank in 0..size {
+            for file in 0..size {
+                if read(1)? != 0 {
+                    occ.set_sq(Square::from_file_rank(file, rank).unwrap());
+                }
+            }
+        }
+
+        let mut used_flats = [0; Player::COUNT];
+        let mut used_caps = [0; Player::COUNT];
+
+        // bitboards iterate in the same order the squares were written in
+        for sq in occ {
+            let top = PieceType::from_raw(read(2)? as u8).ok_or(UnpackError::InvalidPiece)?;
+
+            let mut height = read(3)? as u32;
+            if height == PACKED_HEIGHT_ESCAPE {
+                height = read(7)? as u32;
+            }
+
+            if height == 0 || height as usize > Stacks::MAX_HEIGHT {
+                return Err(UnpackError::InvalidHeight);
+            }
+
+            let mut players = read(height.min(64))? as u128;
+            if height > 64 {
+                players |= (read(height - 64)? as u128) << 64;
+            }
+
+            for idx in 0..height {
+                let player = Player::from_raw(((players >> idx) & 0x1) as u8).unwrap();
+
+                if idx == height - 1 {
+                    pos.stacks.push(sq, top, player);
+                    if top == PieceType::Capstone {
+                        used_caps[player.idx()] += 1;
+                        continue;
+                    }
+                } else {
+                    pos.stacks.push(sq, PieceType::Flat, player);
+                }
+
+                used_flats[player.idx()] += 1;
+            }
+        }
+
+        for player in [Player::P1, Player::P2] {
+            if used_flats[player.idx()] > pos.flats_in_hand(player) as u32
+                || used_caps[player.idx()] > pos.caps_in_hand(player) as u32
+            {
+                return Err(UnpackError::TooManyPieces);
+            }
+        }
+
+        pos.regen();
+
+        // the reserves are redundant, but catch corruption the checksum might miss
+        if pos.flats_in_hand != flats_in_hand || pos.caps_in_hand != caps_in_hand {
+            return Err(UnpackError::ReserveMismatch);
+        }
+
+        let len = reader.bytes_read();
+
+        let checksum = read_u16(&mut &src[len..]).map_err(|_| UnpackError::Truncated)?;
+        if checksum != packed_checksum(&src[..len]) {
+            return Err(UnpackError::ChecksumMismatch);
+        }
+
+        Ok((pos, len + 2))
+    }
+
     fn regen(&mut self) {
         self.players.fill(Bitboard::empty());
         self.pieces.fill(Bitboard::empty());
 
-        self.flats_in_hand.fill(30);
-        self.caps_in_hand.fill(1);
+        let (flats, caps) = STARTING_RESERVES[self.size as usize];
+
+        self.flats_in_hand.fill(flats);
+        self.caps_in_hand.fill(caps);
 
         for sq_idx in 0..Square::COUNT {
             let sq = Square::from_raw(sq_idx as u8).unwrap();
@@ -786,6 +1285,31 @@ pub enum TpsError {
     WrongNumberOfFiles,
     InvalidStm,
     InvalidFullmove,
+    TooManyPieces,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum UnpackError {
+    Truncated,
+    InvalidSize,
+    InvalidHalfKomi,
+    InvalidPiece,
+    InvalidHeight,
+    TooManyPieces,
+    ReserveMismatch,
+    ChecksumMismatch,
+}
+
+const PACKED_HEIGHT_ESCAPE: u32 = 7;
+
+// folded down to 16 bits
+#[must_use]
+fn packed_checksum(bytes: &[u8]) -> u16 {
+    let mut checksum = Checksum::new();
+    checksum.update(bytes);
+
+    let hash = checksum.value();
+    (hash ^ (hash >> 16) ^ (hash >> 32) ^ (hash >> 48)) as u16
 }
 
 impl FromStr for Position {
@@ -796,3 +1320,86 @@ impl FromStr for Position {
         Self::from_tps_parts(&parts)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::road::RoadOrientation;
+
+    #[must_use]
+    fn squares(bb: Bitboard) -> Vec<String> {
+        bb.into_iter().map(|sq| sq.to_string()).collect()
+    }
+
+    #[must_use]
+    fn moves(moves: &[Move]) -> Vec<String> {
+        let mut moves: Vec<_> = moves.iter().map(|mv| mv.to_string()).collect();
+        moves.sort();
+        moves
+    }
+
+    #[test]
+    fn placement_road_threats() {
+        let pos: Position = "x6/x6/x6/2,2,2,2,2,x/x6/1,1,1,1,x,1 1 6".parse().unwrap();
+
+        assert_eq!(squares(pos.road_threats(Player::P1).placements), ["e1"]);
+        // with P1 to move, P2's threats are found through a null move
+        assert_eq!(squares(pos.road_threats(Player::P2).placements), ["f3"]);
+    }
+
+    #[test]
+    fn spread_road_threats() {
+        let pos: Position = "x5/x5/x5/x4,11/1,1,1,1,2 1 8".parse().unwrap();
+
+        let threats = pos.road_threats(Player::P1);
+
+        // d2 joins d1 to the e2 stack, which is already on the edge
+        assert_eq!(squares(threats.placements), ["d2"]);
+        // covering e1, or moving one flat onto d2 while the other stays on e2
+        assert_eq!(moves(&threats.spreads), ["2e2-", "e2-", "e2<"]);
+        assert!(pos.road_threats(Player::P2).is_empty());
+    }
+
+    #[test]
+    fn no_road_threats_in_opening() {
+        let pos: Position = "x4/x4/x4/2,x3 2 1".parse().unwrap();
+
+        assert!(pos.road_threats(Player::P1).is_empty());
+        assert!(pos.road_threats(Player::P2).is_empty());
+    }
+
+    // on 8x8 each rank fills its whole stride, so h2 and a3 are adjacent bits
+    #[test]
+    fn road_threats_do_not_wrap_on_8x8() {
+        let pos: Position = "x8/x8/x8/x8/x8/1,1,1,1,1,1,1,x/x7,1/2,2,2,2,2,2,x2 1 9"
+            .parse()
+            .unwrap();
+
+        assert!(!pos.has_road(Player::P1));
+        assert_eq!(squares(pos.road_threats(Player::P1).placements), ["g2", "h3"]);
+    }
+
+    #[test]
+    fn road_excludes_branches() {
+        let pos: Position = "2,2,x,2,2/x,1,1,x2/1,1,1,1,1/x5/2,2,x3 2 7".parse().unwrap();
+
+        let road = pos.road(Player::P1).unwrap();
+
+        // b4 and c4 touch the road but are not needed to connect the edges
+        assert_eq!(road.orientation, RoadOrientation::Horizontal);
+        assert_eq!(squares(road.squares), ["a3", "b3", "c3", "d3", "e3"]);
+    }
+
+    #[test]
+    fn road_in_both_directions() {
+        let pos: Position = "x,x,1,x2/x,1,1,x2/1,1,1,1,1/x,x,1,x2/2,2,1,2,2 2 9".parse().unwrap();
+
+        let road = pos.road(Player::P1).unwrap();
+
+        assert_eq!(road.orientation, RoadOrientation::Both);
+        assert_eq!(
+            squares(road.squares),
+            ["c1", "c2", "a3", "b3", "c3", "d3", "e3", "c4", "c5"]
+        );
+    }
+}
@@ -0,0 +1,432 @@
+/*
+ * syntaks, a TEI Tak engine
+ * Copyright (c) 2026 Ciekce
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use crate::board::Position;
+use crate::core::{MAX_SIZE, MIN_SIZE};
+use crate::keys::{KEY_SCHEME_FINGERPRINT, Sfc64};
+use crate::ptn::{GameResult, PtnGame, split_games};
+use crate::symmetry::canonical;
+use crate::takmove::Move;
+use crate::util::checksum::Checksum;
+use crate::util::le::{read_u16, read_u32, read_u64};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+const FILE_MAGIC: [u8; 8] = *b"SYNTAKBK";
+// bump whenever the entry layout or keying changes
+const FILE_VERSION: u32 = 2;
+
+const HEADER_SIZE: usize = FILE_MAGIC.len() + 4 + 8 + 4 + 4 + 8;
+// key, move and weight
+const ENTRY_SIZE: usize = 8 + 2 + 4;
+
+const DEFAULT_BUILD_PLIES: u16 = 16;
+const DEFAULT_MIN_WEIGHT: u32 = 2;
+
+#[derive(Debug)]
+pub(crate) enum BookError {
+    Io(std::io::Error),
+    InvalidMagic,
+    UnsupportedVersion(u32),
+    KeySchemeMismatch,
+    InvalidSize(u32),
+    InvalidHalfKomi(u32),
+    Truncated,
+    InvalidMove(usize),
+    Unsorted,
+    ChecksumMismatch,
+}
+
+impl From<std::io::Error> for BookError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl Display for BookError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{}", err),
+            Self::InvalidMagic => write!(f, "not a book file"),
+            Self::UnsupportedVersion(version) => write!(f, "unsupported version {}", version),
+            Self::KeySchemeMismatch => write!(f, "built with different position keys"),
+            Self::InvalidSize(size) => write!(f, "invalid board size {}", size),
+            Self::InvalidHalfKomi(half_komi) => write!(f, "invalid half komi {}", half_komi),
+            Self::Truncated => write!(f, "truncated"),
+            Self::InvalidMove(idx) => write!(f, "invalid move in entry {}", idx),
+            Self::Unsorted => write!(f, "entries are not sorted"),
+            Self::ChecksumMismatch => write!(f, "checksum mismatch"),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct BookMove {
+    pub mv: Move,
+    pub weight: u32,
+}
+
+// positions are keyed by their canonical key, with moves stored in the canonical
+// orientation, so mirrored lines share their entries
+pub(crate) struct Book {
+    size: u32,
+    half_komi: u32,
+    // sorted by key, then by descending weight
+    entries: Vec<(u64, BookMove)>,
+}
+
+impl Book {
+    // header: magic, version, key fingerprint, size, half komi, entry count
+    // followed by the entries in order, then a checksum over everything before it
+    pub fn save(&self, path: &Path) -> Result<(), BookError> {
+        let mut bytes = Vec::with_capacity(HEADER_SIZE + self.entries.len() * ENTRY_SIZE + 8);
+
+        bytes.extend_from_slice(&FILE_MAGIC);
+        bytes.extend_from_slice(&FILE_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&KEY_SCHEME_FINGERPRINT.to_le_bytes());
+        bytes.extend_from_slice(&self.size.to_le_bytes());
+        bytes.extend_from_slice(&self.half_komi.to_le_bytes());
+        bytes.extend_from_slice(&(self.entries.len() as u64).to_le_bytes());
+
+        for (key, book_move) in self.entries.iter() {
+            bytes.extend_from_slice(&key.to_le_bytes());
+            bytes.extend_from_slice(&book_move.mv.raw().to_le_bytes());
+            bytes.extend_from_slice(&book_move.weight.to_le_bytes());
+        }
+
+        let mut checksum = Checksum::new();
+        checksum.update(&bytes);
+        bytes.extend_from_slice(&checksum.value().to_le_bytes());
+
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(&bytes)?;
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, BookError> {
+        let bytes = std::fs::read(path)?;
+
+        if bytes.len() < FILE_MAGIC.len() || bytes[..FILE_MAGIC.len()] != FILE_MAGIC {
+            return Err(BookError::InvalidMagic);
+        }
+
+        if bytes.len() < HEADER_SIZE + 8 {
+            return Err(BookError::Truncated);
+        }
+
+        let mut header = &bytes[FILE_MAGIC.len()..HEADER_SIZE];
+
+        let version = read_u32(&mut header)?;
+        if version != FILE_VERSION {
+            return Err(BookError::UnsupportedVersion(version));
+        }
+
+        if read_u64(&mut header)? != KEY_SCHEME_FINGERPRINT {
+            return Err(BookError::KeySchemeMismatch);
+        }
+
+        let size = read_u32(&mut header)?;
+        if !(MIN_SIZE..=MAX_SIZE).contains(&size) {
+            return Err(BookError::InvalidSize(size));
+        }
+
+        let half_komi = read_u32(&mut header)?;
+        if half_komi > Position::MAX_HALF_KOMI {
+            return Err(BookError::InvalidHalfKomi(half_komi));
+        }
+
+        let count = read_u64(&mut header)? as usize;
+
+        let checksum_offset = count
+            .checked_mul(ENTRY_SIZE)
+            .and_then(|len| len.checked_add(HEADER_SIZE))
+            .filter(|&offset| offset + 8 <= bytes.len())
+            .ok_or(BookError::Truncated)?;
+
+        let mut checksum = Checksum::new();
+        checksum.update(&bytes[..checksum_offset]);
+
+        if read_u64(&mut &bytes[checksum_offset..])? != checksum.value() {
+            return Err(BookError::ChecksumMismatch);
+        }
+
+        let mut reader = &bytes[HEADER_SIZE..checksum_offset];
+        let mut entries = Vec::with_capacity(count);
+
+        for idx in 0..count {
+            let key = read_u64(&mut reader)?;
+            let mv = read_u16(&mut reader)?;
+            let weight = read_u32(&mut reader)?;
+
+            let mv = Move::from_raw(mv).ok_or(BookError::InvalidMove(idx))?;
+
+            if entries.last().is_some_and(|&(prev, _)| prev > key) {
+                return Err(BookError::Unsorted);
+            }
+
+            entries.push((key, BookMove { mv, weight }));
+        }
+
+        Ok(Self {
+            size,
+            half_komi,
+            entries,
+        })
+    }
+
+    #[must_use]
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    #[must_use]
+    pub fn half_komi(&self) -> u32 {
+        self.half_komi
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    // keys do not include the size or komi
+    #[must_use]
+    pub fn supports(&self, pos: &Position) -> bool {
+        pos.size() == self.size && pos.half_komi() == self.half_komi
+    }
+
+    // legal moves for `pos`, heaviest first
+    #[must_use]
+    pub fn moves(&self, pos: &Position) -> Vec<BookMove> {
+        if !self.supports(pos) {
+            return Vec::new();
+        }
+
+        let (key, sym) = canonical(pos);
+        let start = self.entries.partition_point(|&(entry_key, _)| entry_key < key);
+
+        self.entries[start..]
+            .iter()
+            .take_while(|&&(entry_key, _)| entry_key == key)
+            .map(|&(_, book_move)| BookMove {
+                mv: sym.inverse().apply_move(book_move.mv, pos.size()),
+                weight: book_move.weight,
+            })
+            .filter(|book_move| book_move.weight > 0 && pos.is_legal(book_move.mv))
+            .collect()
+    }
+
+    // a random move with probability proportional to its weight
+    #[must_use]
+    pub fn probe(&self, pos: &Position, rng: &mut Sfc64) -> Option<Move> {
+        let moves = self.moves(pos);
+
+        let total: u64 = moves.iter().map(|book_move| book_move.weight as u64).sum();
+        if total == 0 {
+            return None;
+        }
+
+        let mut pick = rng.next_u64() % total;
+
+        for book_move in moves {
+            if pick < book_move.weight as u64 {
+                return Some(book_move.mv);
+            }
+            pick -= book_move.weight as u64;
+        }
+
+        unreachable!()
+    }
+}
+
+// counts how often each move was played from each position in the opening,
+// with a win for the side that played it counting double
+pub(crate) struct BookBuilder {
+    size: u32,
+    half_komi: u32,
+    max_plies: u16,
+    weights: HashMap<(u64, u16), u32>,
+}
+
+impl BookBuilder {
+    pub fn new(size: u32, half_komi: u32, max_plies: u16) -> Self {
+        Self {
+            size,
+            half_komi,
+            max_plies,
+            weights: HashMap::new(),
+        }
+    }
+
+    // games from other sizes, komis or from a TPS are skipped
+    pub fn add_game(&mut self, game: &PtnGame) -> bool {
+        let mut pos = Position::startpos(self.size);
+        pos.set_half_komi(self.half_komi);
+
+        if *game.start_pos() != pos {
+            return false;
+        }
+
+        let winner = match game.result() {
+            Some(GameResult::RoadWin(player) | GameResult::FlatWin(player) | GameResult::OtherWin(player)) => {
+                Some(player)
+            }
+            _ => None,
+        };
+
+        for &mv in game.moves().iter().take(self.max_plies as usize) {
+            let mover = pos.stm();
+            let weight = if winner == Some(mover) { 2 } else { 1 };
+
+            let (key, sym) = canonical(&pos);
+            let canonical_move = sym.apply_move(mv, self.size);
+
+            *self.weights.entry((key, canonical_move.raw())).or_default() += weight;
+
+            pos = pos.apply_move(mv);
+        }
+
+        true
+    }
+
+    #[must_use]
+    pub fn build(self, min_weight: u32) -> Book {
+        let mut entries: Vec<_> = self
+            .weights
+            .into_iter()
+            .filter(|&(_, weight)| weight >= min_weight)
+            .map(|((key, mv), weight)| {
+                let mv = Move::from_raw(mv).unwrap();
+                (key, BookMove { mv, weight })
+            })
+            .collect();
+
+        // ties are broken by move for a deterministic file
+        entries.sort_by_key(|&(key, book_move)| (key, std::cmp::Reverse(book_move.weight), book_move.mv.raw()));
+
+        Book {
+            size: self.size,
+            half_komi: self.half_komi,
+            entries,
+        }
+    }
+}
+
+// makebook <ptn collection> <output> [size <n>] [halfkomi <n>] [plies <n>] [minweight <n>]
+pub fn run_build(args: &[String]) -> bool {
+    let [input, output, options @ ..] = args else {
+        eprintln!("Missing input or output file");
+        return false;
+    };
+
+    let mut size = Position::DEFAULT_SIZE;
+    let mut half_komi = Position::DEFAULT_HALF_KOMI;
+    let mut max_plies = DEFAULT_BUILD_PLIES;
+    let mut min_weight = DEFAULT_MIN_WEIGHT;
+
+    let mut i = 0;
+    while i < options.len() {
+        let Some(value) = options.get(i + 1) else {
+            eprintln!("Missing value for '{}'", options[i]);
+            return false;
+        };
+
+        let valid = match options[i].as_str() {
+            "size" => value
+                .parse::<u32>()
+                .ok()
+                .filter(|value| (MIN_SIZE..=MAX_SIZE).contains(value))
+                .map(|value| size = value)
+                .is_some(),
+            "halfkomi" => value
+                .parse::<u32>()
+                .ok()
+                .filter(|&value| value <= Position::MAX_HALF_KOMI)
+                .map(|value| half_komi = value)
+                .is_some(),
+            "plies" => value.parse().map(|value| max_plies = value).is_ok(),
+            "minweight" => value.parse().map(|value| min_weight = value).is_ok(),
+            unknown => {
+                eprintln!("Unknown option '{}'", unknown);
+                return false;
+            }
+        };
+
+        if !valid {
+            eprintln!("Invalid value '{}' for '{}'", value, options[i]);
+            return false;
+        }
+
+        i += 2;
+    }
+
+    let ptn = match std::fs::read_to_string(input) {
+        Ok(ptn) => ptn,
+        Err(err) => {
+            eprintln!("Failed to read '{}': {}", input, err);
+            return false;
+        }
+    };
+
+    let mut builder = BookBuilder::new(size, half_komi, max_plies);
+
+    let mut used = 0;
+    let mut skipped = 0;
+
+    for (idx, game) in split_games(&ptn).into_iter().enumerate() {
+        match game.parse::<PtnGame>() {
+            Ok(game) => {
+                if builder.add_game(&game) {
+                    used += 1;
+                } else {
+                    skipped += 1;
+                }
+            }
+            Err(err) => {
+                eprintln!("Skipping game {}: {:?}", idx + 1, err);
+                skipped += 1;
+            }
+        }
+    }
+
+    let book = builder.build(min_weight);
+
+    if let Err(err) = book.save(Path::new(output)) {
+        eprintln!("Failed to write '{}': {}", output, err);
+        return false;
+    }
+
+    println!(
+        "Built a book of {} moves from {} games, skipped {}",
+        book.len(),
+        used,
+        skipped
+    );
+
+    true
+}
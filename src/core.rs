@@ -25,6 +25,9 @@ use crate::bitboard::Bitboard;
 use std::fmt::{Display, Formatter, Write};
 use std::str::FromStr;
 
+pub const MIN_SIZE: u32 = 3;
+pub const MAX_SIZE: u32 = Square::STRIDE;
+
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
 #[repr(u8)]
 pub enum Player {
@@ -205,7 +208,7 @@ impl Direction {
 
     #[must_use]
     pub const fn offset(self) -> i8 {
-        [6, -6, -1, 1][self.idx()]
+        [8, -8, -1, 1][self.idx()]
     }
 }
 
@@ -224,16 +227,21 @@ impl Display for Direction {
 #[repr(u8)]
 #[rustfmt::skip]
 pub enum Square {
-    A1, B1, C1, D1, E1, F1,
-    A2, B2, C2, D2, E2, F2,
-    A3, B3, C3, D3, E3, F3,
-    A4, B4, C4, D4, E4, F4,
-    A5, B5, C5, D5, E5, F5,
-    A6, B6, C6, D6, E6, F6,
+    A1, B1, C1, D1, E1, F1, G1, H1,
+    A2, B2, C2, D2, E2, F2, G2, H2,
+    A3, B3, C3, D3, E3, F3, G3, H3,
+    A4, B4, C4, D4, E4, F4, G4, H4,
+    A5, B5, C5, D5, E5, F5, G5, H5,
+    A6, B6, C6, D6, E6, F6, G6, H6,
+    A7, B7, C7, D7, E7, F7, G7, H7,
+    A8, B8, C8, D8, E8, F8, G8, H8,
 }
 
 impl Square {
-    pub const COUNT: usize = 36;
+    // squares are laid out on an 8x8 grid regardless of the board size,
+    // smaller boards just use the lower left corner
+    pub const STRIDE: u32 = 8;
+    pub const COUNT: usize = (Self::STRIDE * Self::STRIDE) as usize;
 
     #[must_use]
     pub const fn from_raw(raw: u8) -> Option<Self> {
@@ -247,10 +255,10 @@ impl Square {
 
     #[must_use]
     pub const fn from_file_rank(file: u32, rank: u32) -> Option<Self> {
-        if file >= 6 || rank >= 6 {
+        if file >= Self::STRIDE || rank >= Self::STRIDE {
             None
         } else {
-            Some(Self::from_raw((rank as u8 * 6) + file as u8).unwrap())
+            Some(Self::from_raw((rank * Self::STRIDE + file) as u8).unwrap())
         }
     }
 
@@ -266,12 +274,12 @@ impl Square {
 
     #[must_use]
     pub const fn rank(self) -> u32 {
-        self.raw() as u32 / 6
+        self.raw() as u32 / Self::STRIDE
     }
 
     #[must_use]
     pub const fn file(self) -> u32 {
-        self.raw() as u32 % 6
+        self.raw() as u32 % Self::STRIDE
     }
 
     #[must_use]
@@ -292,19 +300,10 @@ impl Square {
     #[must_use]
     pub const fn shift_checked(self, dir: Direction) -> Option<Self> {
         match dir {
-            Direction::Left => {
-                if self.file() == 0 {
-                    return None;
-                }
-            }
-            Direction::Right => {
-                if self.file() == 5 {
-                    return None;
-                }
-            }
-            _ => {}
+            Direction::Left if self.file() == 0 => None,
+            Direction::Right if self.file() == Self::STRIDE - 1 => None,
+            _ => self.shift(dir),
         }
-        self.shift(dir)
     }
 
     #[must_use]
@@ -343,12 +342,12 @@ impl FromStr for Square {
         }
 
         let file = bytes[0];
-        if !(b'a'..=b'f').contains(&file) {
+        if !(b'a'..=b'h').contains(&file) {
             return Err(SquareStrError::InvalidFile);
         }
 
         let rank = bytes[1];
-        if !(b'1'..=b'6').contains(&rank) {
+        if !(b'1'..=b'8').contains(&rank) {
             return Err(SquareStrError::InvalidRank);
         }
 
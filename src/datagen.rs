@@ -0,0 +1,502 @@
+/*
+ * syntaks, a TEI Tak engine
+ * Copyright (c) 2026 Ciekce
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use crate::board::Position;
+use crate::core::{MAX_SIZE, MIN_SIZE, PieceType, Player};
+use crate::keys::Sfc64;
+use crate::limit::Limits;
+use crate::movegen::generate_moves;
+use crate::nnue::Network;
+use crate::packed::{Label, PackedWriter};
+use crate::ptn::{GameResult, PtnGame};
+use crate::search::{MAX_DEPTH, SCORE_WIN, Score, Searcher};
+use crate::symmetry::canonical_key;
+use crate::takmove::Move;
+use crate::tei::TeiOptions;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, mpsc};
+use std::thread;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+const DEFAULT_GAMES: usize = 1000;
+const DEFAULT_THREADS: usize = 1;
+const DEFAULT_SOFT_NODES: usize = 5000;
+const DEFAULT_RANDOM_PLIES: u32 = 6;
+
+// hard node limit, as a multiple of the soft limit
+const HARD_NODES_FACTOR: usize = 8;
+
+const TT_SIZE_MIB: usize = 16;
+
+// games still going at this point are drawn
+const MAX_GAME_PLIES: u16 = 400;
+
+// both players' searches must agree on the winner for this many plies in a row
+const WIN_ADJ_SCORE: Score = 1000;
+const WIN_ADJ_PLIES: u32 = 4;
+
+const REPORT_INTERVAL: usize = 100;
+
+// give up if this many random openings in a row end the game
+const MAX_OPENING_ATTEMPTS: u32 = 1000;
+
+#[derive(Copy, Clone, Debug)]
+struct Config {
+    games: usize,
+    soft_nodes: usize,
+    size: u32,
+    half_komi: u32,
+    random_plies: u32,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Format {
+    Text,
+    Packed,
+}
+
+struct Game {
+    // P1-relative scores
+    positions: Vec<(Position, Score)>,
+    result: GameResult,
+    ptn: PtnGame,
+}
+
+#[must_use]
+fn random_move(rng: &mut Sfc64, moves: &[Move]) -> Move {
+    moves[(rng.next_u64() % moves.len() as u64) as usize]
+}
+
+// None if the random moves happened to end the game
+#[must_use]
+fn random_opening(rng: &mut Sfc64, config: &Config) -> Option<(PtnGame, Vec<u64>)> {
+    let mut pos = Position::startpos(config.size);
+    pos.set_half_komi(config.half_komi);
+
+    let mut game = PtnGame::new(pos);
+
+    let mut key_history = Vec::with_capacity(MAX_GAME_PLIES as usize);
+    let mut moves = Vec::with_capacity(256);
+
+    for _ in 0..config.random_plies {
+        let pos = *game.final_pos();
+
+        generate_moves(&mut moves, &pos);
+
+        key_history.push(pos.key());
+        game.push_move(random_move(rng, &moves));
+
+        if game.result().is_some() {
+            return None;
+        }
+    }
+
+    Some((game, key_history))
+}
+
+// road threats and crushes are left to the search, their static eval is meaningless
+#[must_use]
+fn is_quiet(pos: &Position, best_move: Move) -> bool {
+    let is_crush = best_move.is_spread() && pos.stacks().top(best_move.spread_dest()) == Some(PieceType::Wall);
+
+    pos.placement_road_threats(pos.stm()).is_empty()
+        && pos.placement_road_threats(pos.stm().flip()).is_empty()
+        && !is_crush
+}
+
+#[must_use]
+fn is_drawn_by_repetition(pos: &Position, key_history: &[u64]) -> bool {
+    key_history.iter().filter(|&&key| key == pos.key()).count() >= 2
+}
+
+// None if no random opening left the game running
+#[must_use]
+fn play_game(searcher: &mut Searcher, rng: &mut Sfc64, config: &Config) -> Option<Game> {
+    let (mut ptn, mut key_history) = (0..MAX_OPENING_ATTEMPTS).find_map(|_| random_opening(rng, config))?;
+
+    let mut pos = *ptn.final_pos();
+
+    searcher.reset();
+
+    let options = TeiOptions::default();
+
+    let mut positions = Vec::with_capacity(MAX_GAME_PLIES as usize);
+
+    // P1-relative, positive while P1 is winning
+    let mut win_streak = 0i32;
+
+    let result = loop {
+        let start_time = Instant::now();
+
+        let mut limits = Limits::new(start_time);
+        limits.set_soft_nodes(config.soft_nodes);
+        limits.set_nodes(config.soft_nodes * HARD_NODES_FACTOR);
+
+        searcher.start_search(&pos, &key_history, start_time, limits, MAX_DEPTH, &[], &options, false);
+        searcher.wait();
+
+        let search_result = searcher.last_result().unwrap();
+
+        let stm = pos.stm();
+        let score = search_result.score * stm.sign();
+
+        if search_result.score.abs() > SCORE_WIN {
+            let winner = if search_result.score > 0 { stm } else { stm.flip() };
+            break GameResult::OtherWin(winner);
+        }
+
+        win_streak = if score >= WIN_ADJ_SCORE {
+            win_streak.max(0) + 1
+        } else if score <= -WIN_ADJ_SCORE {
+            win_streak.min(0) - 1
+        } else {
+            0
+        };
+
+        if win_streak.unsigned_abs() >= WIN_ADJ_PLIES {
+            let winner = if win_streak > 0 { Player::P1 } else { Player::P2 };
+            break GameResult::OtherWin(winner);
+        }
+
+        if is_quiet(&pos, search_result.mv) {
+            positions.push((pos, score));
+        }
+
+        let mv = search_result.mv;
+
+        key_history.push(pos.key());
+        ptn.push_move(mv);
+        pos = *ptn.final_pos();
+
+        if let Some(result) = ptn.result() {
+            break result;
+        }
+
+        if (mv.is_spread() && is_drawn_by_repetition(&pos, &key_history)) || pos.ply() >= MAX_GAME_PLIES {
+            break GameResult::Draw;
+        }
+    };
+
+    ptn.set_result(Some(result));
+
+    Some(Game { positions, result, ptn })
+}
+
+// false if the worker gave up on finding a random opening
+#[must_use]
+fn run_worker(
+    config: &Config,
+    seed: u64,
+    network: Option<Arc<Network>>,
+    next_game: &AtomicUsize,
+    sender: mpsc::Sender<Game>,
+) -> bool {
+    let mut searcher = Searcher::new();
+    searcher.set_tt_size(TT_SIZE_MIB);
+    searcher.set_network(network);
+    searcher.set_quiet(true);
+
+    let mut rng = Sfc64::new(seed);
+
+    while next_game.fetch_add(1, Ordering::Relaxed) < config.games {
+        let Some(game) = play_game(&mut searcher, &mut rng, config) else {
+            // stop the other workers too
+            next_game.store(config.games, Ordering::Relaxed);
+            return false;
+        };
+
+        // the writer has given up
+        if sender.send(game).is_err() {
+            break;
+        }
+    }
+
+    true
+}
+
+enum Output<W: Write> {
+    // one line per position: TPS, P1-relative score in centiflats, and the game's result
+    Text(W),
+    // the same, as labelled records in the packed position format
+    Packed(PackedWriter<W>),
+}
+
+impl<W: Write> Output<W> {
+    fn new(writer: W, format: Format) -> std::io::Result<Self> {
+        match format {
+            Format::Text => Ok(Self::Text(writer)),
+            Format::Packed => PackedWriter::new(writer).map(Self::Packed),
+        }
+    }
+
+    fn write(&mut self, pos: &Position, score: Score, result: GameResult) -> std::io::Result<()> {
+        match self {
+            Self::Text(writer) => writeln!(writer, "{} {} {}", pos.tps(), score, result),
+            Self::Packed(writer) => writer.write(pos, Some(Label { score, result })),
+        }
+    }
+
+    fn finish(self) -> std::io::Result<()> {
+        match self {
+            Self::Text(mut writer) => writer.flush(),
+            Self::Packed(writer) => writer.finish().map(|_| ()),
+        }
+    }
+}
+
+// positions are only written the first time they, or any mirror image of them, come up.
+// the games themselves are optionally written as PTN
+fn write_games(
+    receiver: mpsc::Receiver<Game>,
+    mut output: Output<impl Write>,
+    mut ptn_writer: Option<&mut impl Write>,
+    config: &Config,
+) -> std::io::Result<()> {
+    let start = Instant::now();
+
+    let mut games = 0;
+    let mut positions = 0;
+
+    let mut seen = HashSet::new();
+
+    for game in receiver {
+        for (pos, score) in game.positions.iter() {
+            if seen.insert(canonical_key(pos)) {
+                output.write(pos, *score, game.result)?;
+                positions += 1;
+            }
+        }
+
+        if let Some(ptn_writer) = ptn_writer.as_mut() {
+            writeln!(ptn_writer, "{}", game.ptn)?;
+        }
+
+        games += 1;
+
+        if games % REPORT_INTERVAL == 0 || games == config.games {
+            let time = start.elapsed().as_secs_f64();
+            println!(
+                "Games {} positions {} time {:.1}s positions/s {:.0}",
+                games,
+                positions,
+                time,
+                positions as f64 / time
+            );
+        }
+    }
+
+    if let Some(ptn_writer) = ptn_writer {
+        ptn_writer.flush()?;
+    }
+
+    output.finish()
+}
+
+// datagen <output> [games <n>] [threads <n>] [nodes <n>] [size <n>] [halfkomi <n>]
+//     [randomplies <n>] [seed <n>] [evalfile <path>] [ptn <path>] [format <text|packed>]
+pub fn run(args: &[String]) -> bool {
+    let Some(path) = args.first() else {
+        eprintln!("Missing output file");
+        return false;
+    };
+
+    let mut config = Config {
+        games: DEFAULT_GAMES,
+        soft_nodes: DEFAULT_SOFT_NODES,
+        size: Position::DEFAULT_SIZE,
+        half_komi: Position::DEFAULT_HALF_KOMI,
+        random_plies: DEFAULT_RANDOM_PLIES,
+    };
+
+    let mut threads = DEFAULT_THREADS;
+    let mut seed = None;
+    let mut eval_file = None;
+    let mut ptn_path = None;
+    let mut format = Format::Text;
+
+    let mut i = 1;
+    while i < args.len() {
+        let Some(value) = args.get(i + 1) else {
+            eprintln!("Missing value for '{}'", args[i]);
+            return false;
+        };
+
+        let valid = match args[i].as_str() {
+            "games" => value.parse().map(|value| config.games = value).is_ok(),
+            "threads" => value
+                .parse::<usize>()
+                .ok()
+                .filter(|&value| value > 0)
+                .map(|value| threads = value)
+                .is_some(),
+            "nodes" => value
+                .parse::<usize>()
+                .ok()
+                .filter(|&value| value > 0)
+                .map(|value| config.soft_nodes = value)
+                .is_some(),
+            "size" => value
+                .parse::<u32>()
+                .ok()
+                .filter(|value| (MIN_SIZE..=MAX_SIZE).contains(value))
+                .map(|value| config.size = value)
+                .is_some(),
+            "halfkomi" => value
+                .parse::<u32>()
+                .ok()
+                .filter(|&value| value <= Position::MAX_HALF_KOMI)
+                .map(|value| config.half_komi = value)
+                .is_some(),
+            "randomplies" => value.parse().map(|value| config.random_plies = value).is_ok(),
+            "seed" => value.parse::<u64>().map(|value| seed = Some(value)).is_ok(),
+            "evalfile" => {
+                eval_file = Some(value.clone());
+                true
+            }
+            "ptn" => {
+                ptn_path = Some(value.clone());
+                true
+            }
+            "format" => match value.as_str() {
+                "text" => {
+                    format = Format::Text;
+                    true
+                }
+                "packed" => {
+                    format = Format::Packed;
+                    true
+                }
+                _ => false,
+            },
+            unknown => {
+                eprintln!("Unknown option '{}'", unknown);
+                return false;
+            }
+        };
+
+        if !valid {
+            eprintln!("Invalid value '{}' for '{}'", value, args[i]);
+            return false;
+        }
+
+        i += 2;
+    }
+
+    let network = match eval_file {
+        Some(eval_file) => match Network::load(Path::new(&eval_file)) {
+            Ok(network) => {
+                if network.size() != config.size || network.half_komi() != config.half_komi {
+                    eprintln!(
+                        "Network '{}' is for size {} and half komi {}",
+                        eval_file,
+                        network.size(),
+                        network.half_komi()
+                    );
+                    return false;
+                }
+                Some(Arc::new(network))
+            }
+            Err(err) => {
+                eprintln!("Failed to load network '{}': {}", eval_file, err);
+                return false;
+            }
+        },
+        None => None,
+    };
+
+    let seed = seed.unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_nanos() as u64)
+            .unwrap_or(0)
+    });
+
+    let output = match File::create(path).and_then(|file| Output::new(BufWriter::new(file), format)) {
+        Ok(output) => output,
+        Err(err) => {
+            eprintln!("Failed to create '{}': {}", path, err);
+            return false;
+        }
+    };
+
+    let mut ptn_writer = match &ptn_path {
+        Some(ptn_path) => match File::create(ptn_path) {
+            Ok(file) => Some(BufWriter::new(file)),
+            Err(err) => {
+                eprintln!("Failed to create '{}': {}", ptn_path, err);
+                return false;
+            }
+        },
+        None => None,
+    };
+
+    println!(
+        "Generating {} games of size {} with half komi {} on {} threads, {} soft nodes per move, seed {}",
+        config.games, config.size, config.half_komi, threads, config.soft_nodes, seed
+    );
+
+    let next_game = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+
+    let (result, found_openings) = thread::scope(|scope| {
+        let workers = (0..threads)
+            .map(|id| {
+                let sender = sender.clone();
+                let network = network.clone();
+                let config = &config;
+                let next_game = &next_game;
+
+                // each worker gets a distinct stream
+                let seed = seed ^ (id as u64 + 1).wrapping_mul(0x9e3779b97f4a7c15);
+
+                scope.spawn(move || run_worker(config, seed, network, next_game, sender))
+            })
+            .collect::<Vec<_>>();
+
+        drop(sender);
+
+        let result = write_games(receiver, output, ptn_writer.as_mut(), &config);
+        let found_openings = workers.into_iter().all(|worker| worker.join().unwrap());
+
+        (result, found_openings)
+    });
+
+    if !found_openings {
+        eprintln!(
+            "Gave up after {} random openings of {} plies in a row ended the game",
+            MAX_OPENING_ATTEMPTS, config.random_plies
+        );
+        return false;
+    }
+
+    if let Err(err) = result {
+        eprintln!("Failed to write output: {}", err);
+        return false;
+    }
+
+    true
+}
@@ -21,23 +21,61 @@
  * SOFTWARE.
  */
 
-use std::array;
-
 use crate::bitboard::Bitboard;
 use crate::board::Position;
-use crate::core::{Direction, Piece, PieceType, Player, Square};
+use crate::core::{Direction, MAX_SIZE, MIN_SIZE, Piece, PieceType, Player, Square};
+use crate::nnue::Network;
 use crate::search::Score;
+use crate::tunable;
+use std::path::Path;
+
+const MAX_RINGS: usize = MAX_SIZE as usize - 1;
 
+const RING_VALUE_COUNT: usize = 5;
+
+// rings of squares at increasing distance from the centre, with their ring value index, indexed by board size
 #[static_init::dynamic]
-static RINGS: [Bitboard; 5] = {
-    let mut covered = Bitboard::from_raw(1 << 14 | 1 << 15 | 1 << 20 | 1 << 21);
-    let mut curr = covered;
-    array::from_fn(|_| {
-        let r = curr;
-        curr = (curr << 6 | curr >> 6 | curr << 1 | curr >> 1) & !covered;
-        covered |= curr;
-        r
-    })
+static RINGS: [[(Bitboard, usize); MAX_RINGS]; MAX_SIZE as usize + 1] = {
+    let mut result = [[(Bitboard::empty(), 0); MAX_RINGS]; MAX_SIZE as usize + 1];
+
+    for size in MIN_SIZE..=MAX_SIZE {
+        let board = Bitboard::board(size);
+
+        let lo = (size - 1) / 2;
+        let hi = size / 2;
+
+        let mut covered = Bitboard::empty();
+        for rank in lo..=hi {
+            for file in lo..=hi {
+                covered.set_sq(Square::from_file_rank(file, rank).unwrap());
+            }
+        }
+
+        let mut rings = [Bitboard::empty(); MAX_RINGS];
+        let mut ring_count = 0;
+
+        let mut curr = covered;
+        while !curr.is_empty() {
+            rings[ring_count] = curr;
+            ring_count += 1;
+
+            curr = (curr.shift(Direction::Up)
+                | curr.shift(Direction::Down)
+                | curr.shift(Direction::Left)
+                | curr.shift(Direction::Right))
+                & board
+                & !covered;
+            covered |= curr;
+        }
+
+        // stretch the 6x6 values over however many rings this size has
+        for (idx, &ring) in rings[..ring_count].iter().enumerate() {
+            let value_idx = idx * (RING_VALUE_COUNT - 1) / (ring_count - 1);
+            result[size as usize][idx] = (ring, value_idx);
+        }
+    }
+
+    result
 };
 
 const ADJACENT_MASKS: [Bitboard; Square::COUNT] = {
@@ -58,24 +96,258 @@ const ADJACENT_MASKS: [Bitboard; Square::COUNT] = {
     masks
 };
 
-#[rustfmt::skip]
-const CAP_PSQT: [Score; Square::COUNT] = [
-    -20,  -5,  -5,  -5,  -5, -20,
-     -5,  10,  18,  18,  10,  -5,
-     -5,  18,  35,  35,  18,  -5,
-     -5,  18,  35,  35,  18,  -5,
-     -5,  10,  18,  18,  10,  -5,
-    -20,  -5,  -5,  -5,  -5, -20,
-];
+// scaled edge distances of each square's file and rank, indexed by board size
+#[static_init::dynamic]
+static CAP_EDGE_DISTS: [[(usize, usize); Square::COUNT]; MAX_SIZE as usize + 1] = {
+    let mut result = [[(0, 0); Square::COUNT]; MAX_SIZE as usize + 1];
+
+    for size in MIN_SIZE..=MAX_SIZE {
+        let max_dist = (size - 1) / 2;
+        let scaled_edge_dist = |coord: u32| {
+            let dist = coord.min(size - 1 - coord);
+            ((dist * 2 + max_dist / 2) / max_dist) as usize
+        };
+
+        for sq in Bitboard::board(size) {
+            let file_dist = scaled_edge_dist(sq.file());
+            let rank_dist = scaled_edge_dist(sq.rank());
+            result[size as usize][sq.idx()] = (file_dist, rank_dist);
+        }
+    }
 
-#[must_use]
-fn static_eval_player(pos: &Position, player: Player, komi: u32) -> Score {
+    result
+};
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(crate) enum EvalTerm {
+    Flats,
+    FlatsInHand,
+    RoadAdjacency,
+    RoadLine,
+    FlatSupport,
+    FlatCaptive,
+    WallSupport,
+    WallCaptive,
+    CapSupport,
+    CapCaptive,
+    IsolatedCap,
+    // by the distances of a square's file and rank from the nearest edge, scaled to 6x6
+    CapPsqt00,
+    CapPsqt01,
+    CapPsqt02,
+    CapPsqt11,
+    CapPsqt12,
+    CapPsqt22,
+    // rings going outwards from the centre, on 6x6
+    Ring0,
+    Ring1,
+    Ring2,
+    Ring3,
+    Ring4,
+    Tempo,
+}
+
+impl EvalTerm {
+    pub const COUNT: usize = 23;
+
+    #[rustfmt::skip]
+    pub const ALL: [Self; Self::COUNT] = [
+        Self::Flats, Self::FlatsInHand, Self::RoadAdjacency, Self::RoadLine,
+        Self::FlatSupport, Self::FlatCaptive, Self::WallSupport, Self::WallCaptive, Self::CapSupport, Self::CapCaptive,
+        Self::IsolatedCap,
+        Self::CapPsqt00, Self::CapPsqt01, Self::CapPsqt02, Self::CapPsqt11, Self::CapPsqt12, Self::CapPsqt22,
+        Self::Ring0, Self::Ring1, Self::Ring2, Self::Ring3, Self::Ring4,
+        Self::Tempo,
+    ];
+
+    #[must_use]
+    pub const fn idx(self) -> usize {
+        self as usize
+    }
+
+    // matches the tunable
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Flats => "flat_value",
+            Self::FlatsInHand => "flat_in_hand",
+            Self::RoadAdjacency => "road_adjacency",
+            Self::RoadLine => "road_line",
+            Self::FlatSupport => "flat_support",
+            Self::FlatCaptive => "flat_captive",
+            Self::WallSupport => "wall_support",
+            Self::WallCaptive => "wall_captive",
+            Self::CapSupport => "cap_support",
+            Self::CapCaptive => "cap_captive",
+            Self::IsolatedCap => "isolated_cap",
+            Self::CapPsqt00 => "cap_psqt_0_0",
+            Self::CapPsqt01 => "cap_psqt_0_1",
+            Self::CapPsqt02 => "cap_psqt_0_2",
+            Self::CapPsqt11 => "cap_psqt_1_1",
+            Self::CapPsqt12 => "cap_psqt_1_2",
+            Self::CapPsqt22 => "cap_psqt_2_2",
+            Self::Ring0 => "ring_0",
+            Self::Ring1 => "ring_1",
+            Self::Ring2 => "ring_2",
+            Self::Ring3 => "ring_3",
+            Self::Ring4 => "ring_4",
+            Self::Tempo => "tempo",
+        }
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub fn weight(self) -> Score {
+        match self {
+            Self::Flats => tunable::flat_value(),
+            Self::FlatsInHand => tunable::flat_in_hand(),
+            Self::RoadAdjacency => tunable::road_adjacency(),
+            Self::RoadLine => tunable::road_line(),
+            Self::FlatSupport => tunable::flat_support(),
+            Self::FlatCaptive => tunable::flat_captive(),
+            Self::WallSupport => tunable::wall_support(),
+            Self::WallCaptive => tunable::wall_captive(),
+            Self::CapSupport => tunable::cap_support(),
+            Self::CapCaptive => tunable::cap_captive(),
+            Self::IsolatedCap => tunable::isolated_cap(),
+            Self::CapPsqt00 => tunable::cap_psqt_0_0(),
+            Self::CapPsqt01 => tunable::cap_psqt_0_1(),
+            Self::CapPsqt02 => tunable::cap_psqt_0_2(),
+            Self::CapPsqt11 => tunable::cap_psqt_1_1(),
+            Self::CapPsqt12 => tunable::cap_psqt_1_2(),
+            Self::CapPsqt22 => tunable::cap_psqt_2_2(),
+            Self::Ring0 => tunable::ring_0(),
+            Self::Ring1 => tunable::ring_1(),
+            Self::Ring2 => tunable::ring_2(),
+            Self::Ring3 => tunable::ring_3(),
+            Self::Ring4 => tunable::ring_4(),
+            Self::Tempo => tunable::tempo(),
+        }
+    }
+
+    // flats are counted in half flats, to account for komi
+    #[must_use]
+    #[inline(always)]
+    pub const fn divisor(self) -> Score {
+        match self {
+            Self::Flats => 2,
+            _ => 1,
+        }
+    }
+
+    #[must_use]
+    const fn cap_psqt(file_dist: usize, rank_dist: usize) -> Self {
+        let (lo, hi) = if file_dist < rank_dist {
+            (file_dist, rank_dist)
+        } else {
+            (rank_dist, file_dist)
+        };
+
+        match (lo, hi) {
+            (0, 0) => Self::CapPsqt00,
+            (0, 1) => Self::CapPsqt01,
+            (0, 2) => Self::CapPsqt02,
+            (1, 1) => Self::CapPsqt11,
+            (1, 2) => Self::CapPsqt12,
+            (2, 2) => Self::CapPsqt22,
+            _ => unreachable!(),
+        }
+    }
+
+    #[must_use]
+    const fn ring(idx: usize) -> Self {
+        match idx {
+            0 => Self::Ring0,
+            1 => Self::Ring1,
+            2 => Self::Ring2,
+            3 => Self::Ring3,
+            4 => Self::Ring4,
+            _ => unreachable!(),
+        }
+    }
+}
+
+// receives each eval term's count for one player
+pub(crate) trait EvalSink {
+    fn add(&mut self, player: Player, term: EvalTerm, count: i32);
+}
+
+struct EvalScore {
+    score: Score,
+}
+
+impl EvalSink for EvalScore {
+    #[inline(always)]
+    fn add(&mut self, player: Player, term: EvalTerm, count: i32) {
+        self.score += count * player.sign() * term.weight() / term.divisor();
+    }
+}
+
+// how often each term applies to a position, P1-relative, for tuning
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct EvalFeatures {
+    pub counts: [i32; EvalTerm::COUNT],
+}
+
+impl EvalFeatures {
+    #[must_use]
+    pub fn new(pos: &Position) -> Self {
+        let mut result = Self {
+            counts: [0; EvalTerm::COUNT],
+        };
+        eval_terms(pos, &mut result);
+        result
+    }
+}
+
+impl EvalSink for EvalFeatures {
+    fn add(&mut self, player: Player, term: EvalTerm, count: i32) {
+        self.counts[term.idx()] += count * player.sign();
+    }
+}
+
+// each player's count for each term, for tracing
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct EvalTrace {
+    pub counts: [[i32; EvalTerm::COUNT]; Player::COUNT],
+}
+
+impl EvalTrace {
+    #[must_use]
+    pub fn new(pos: &Position) -> Self {
+        let mut result = Self {
+            counts: [[0; EvalTerm::COUNT]; Player::COUNT],
+        };
+        eval_terms(pos, &mut result);
+        result
+    }
+
+    // from `player`'s perspective, ignoring the opponent
+    #[must_use]
+    pub fn score(&self, player: Player, term: EvalTerm) -> Score {
+        self.counts[player.idx()][term.idx()] * term.weight() / term.divisor()
+    }
+
+    #[must_use]
+    pub fn total(&self, player: Player) -> Score {
+        EvalTerm::ALL.into_iter().map(|term| self.score(player, term)).sum()
+    }
+}
+
+impl EvalSink for EvalTrace {
+    fn add(&mut self, player: Player, term: EvalTerm, count: i32) {
+        self.counts[player.idx()][term.idx()] += count;
+    }
+}
+
+#[inline(always)]
+fn eval_player_terms<S: EvalSink>(pos: &Position, player: Player, half_komi: u32, sink: &mut S) {
     let flat_bb = pos.player_piece_bb(PieceType::Flat.with_player(player));
-    let flats = (flat_bb.popcount() + komi) as Score;
-    let flats = flats * 75;
+    let half_flats = (2 * flat_bb.popcount() + half_komi) as i32;
+    sink.add(player, EvalTerm::Flats, half_flats);
 
-    let flats_in_hand = pos.flats_in_hand(player) as Score;
-    let flats_in_hand = flats_in_hand * -13;
+    let flats_in_hand = pos.flats_in_hand(player) as i32;
+    sink.add(player, EvalTerm::FlatsInHand, flats_in_hand);
 
     let road_bb = pos.roads(player);
 
@@ -85,17 +357,17 @@ fn static_eval_player(pos: &Position, player: Player, komi: u32) -> Score {
     let line_horz = adj_horz & adj_horz.shift(Direction::Left);
     let line_vert = adj_vert & adj_vert.shift(Direction::Down);
 
-    let adj_value = (adj_horz.popcount() + adj_vert.popcount()) as Score;
-    let line_value = (line_horz.popcount() + line_vert.popcount()) as Score;
+    let adj_count = (adj_horz.popcount() + adj_vert.popcount()) as i32;
+    let line_count = (line_horz.popcount() + line_vert.popcount()) as i32;
 
-    let adj_value = adj_value * 9;
-    let line_value = line_value * 7;
+    sink.add(player, EvalTerm::RoadAdjacency, adj_count);
+    sink.add(player, EvalTerm::RoadLine, line_count);
 
     let stacks = &pos.stacks();
-    let player_flip = if player == Player::P2 { u64::MAX } else { 0 };
+    let player_flip = if player == Player::P2 { u128::MAX } else { 0 };
 
-    let mut support_score = 0;
-    let mut captive_score = 0;
+    // [support, captive], indexed by top piece type
+    let mut stack_counts = [[0; 2]; PieceType::COUNT];
 
     for sq in pos.player_bb(player) {
         let mut height = stacks.height(sq);
@@ -113,59 +385,168 @@ fn static_eval_player(pos: &Position, player: Player, komi: u32) -> Score {
 
         let mask = (1 << (height - 1)) - 1;
 
-        let support_count = (!players & mask).count_ones() as Score;
-        let captive_count = (players & mask).count_ones() as Score;
+        let counts = &mut stack_counts[stacks.top(sq).unwrap().idx()];
+        counts[0] += (!players & mask).count_ones() as i32;
+        counts[1] += (players & mask).count_ones() as i32;
+    }
 
-        match stacks.top(sq).unwrap() {
-            PieceType::Flat => {
-                support_score += support_count * 30;
-                captive_score += captive_count * -40;
-            }
-            PieceType::Wall => {
-                support_score += support_count * 35;
-                captive_score += captive_count * -15;
-            }
-            PieceType::Capstone => {
-                support_score += support_count * 40;
-                captive_score += captive_count * -20;
-            }
-        }
+    for (pt, support, captive) in [
+        (PieceType::Flat, EvalTerm::FlatSupport, EvalTerm::FlatCaptive),
+        (PieceType::Wall, EvalTerm::WallSupport, EvalTerm::WallCaptive),
+        (PieceType::Capstone, EvalTerm::CapSupport, EvalTerm::CapCaptive),
+    ] {
+        let [support_count, captive_count] = stack_counts[pt.idx()];
+        sink.add(player, support, support_count);
+        sink.add(player, captive, captive_count);
     }
 
     let isolated_mask = pos.occ() & !flat_bb;
 
-    let mut psqt_score = 0;
-    let mut isolated_cap_score = 0;
+    let cap_edge_dists = &CAP_EDGE_DISTS[pos.size() as usize];
 
     for cap_sq in pos.player_piece_bb(PieceType::Capstone.with_player(player)) {
-        psqt_score += CAP_PSQT[cap_sq.idx()];
+        let (file_dist, rank_dist) = cap_edge_dists[cap_sq.idx()];
+        sink.add(player, EvalTerm::cap_psqt(file_dist, rank_dist), 1);
 
         let adjacent = ADJACENT_MASKS[cap_sq.idx()];
         if (adjacent & isolated_mask).is_empty() {
-            isolated_cap_score -= 50;
+            sink.add(player, EvalTerm::IsolatedCap, 1);
         }
     }
-
-    flats + flats_in_hand + adj_value + line_value + support_score + captive_score + psqt_score + isolated_cap_score
 }
 
-#[must_use]
-pub fn static_eval(pos: &Position) -> Score {
-    let p1_score = static_eval_player(pos, Player::P1, 0);
-    let p2_score = static_eval_player(pos, Player::P2, Position::KOMI);
+#[inline(always)]
+fn eval_terms<S: EvalSink>(pos: &Position, sink: &mut S) {
+    eval_player_terms(pos, Player::P1, 0, sink);
+    eval_player_terms(pos, Player::P2, pos.half_komi(), sink);
 
     let p1_flat_bb = pos.player_piece_bb(Piece::P1Flat);
     let p2_flat_bb = pos.player_piece_bb(Piece::P2Flat);
 
-    let flat_position_quality_diff = RINGS
-        .iter()
-        .zip([2, 8, -5, -15, -40])
-        .map(|(&ring, value)| {
-            (p1_flat_bb & ring).popcount() as i32 * value - (p2_flat_bb & ring).popcount() as i32 * value
-        })
-        .sum::<i32>();
+    for &(ring, value_idx) in RINGS[pos.size() as usize].iter() {
+        sink.add(
+            Player::P1,
+            EvalTerm::ring(value_idx),
+            (p1_flat_bb & ring).popcount() as i32,
+        );
+        sink.add(
+            Player::P2,
+            EvalTerm::ring(value_idx),
+            (p2_flat_bb & ring).popcount() as i32,
+        );
+    }
+
+    sink.add(pos.stm(), EvalTerm::Tempo, 1);
+}
+
+#[must_use]
+pub(crate) fn static_eval(pos: &Position) -> Score {
+    let mut eval = EvalScore { score: 0 };
+    eval_terms(pos, &mut eval);
+    eval.score * pos.stm().sign()
+}
+
+#[must_use]
+pub(crate) fn format_flats(score: Score) -> String {
+    format!("{:+.2}", score as f64 / 100.0)
+}
+
+// prints each eval term for both players, then the total and the stm-relative static eval
+pub(crate) fn print_trace(pos: &Position) {
+    let trace = EvalTrace::new(pos);
+
+    println!("{:<16} {:>8} {:>8} {:>8}", "Term", "P1", "P2", "P1 - P2");
+
+    for term in EvalTerm::ALL {
+        let p1 = trace.score(Player::P1, term);
+        let p2 = trace.score(Player::P2, term);
+        println!(
+            "{:<16} {:>8} {:>8} {:>8}",
+            term.name(),
+            format_flats(p1),
+            format_flats(p2),
+            format_flats(p1 - p2)
+        );
+    }
+
+    let p1 = trace.total(Player::P1);
+    let p2 = trace.total(Player::P2);
+    println!(
+        "{:<16} {:>8} {:>8} {:>8}",
+        "total",
+        format_flats(p1),
+        format_flats(p2),
+        format_flats(p1 - p2)
+    );
+
+    let static_eval = static_eval(pos);
+    debug_assert_eq!(static_eval, (p1 - p2) * pos.stm().sign());
+
+    println!();
+    println!("Static eval (stm-relative, handcrafted): {}", format_flats(static_eval));
+}
+
+// eval <tps> [evalfile <path>]
+pub fn run(args: &[String]) -> bool {
+    let Some(tps) = args.first() else {
+        eprintln!("Missing TPS");
+        return false;
+    };
+
+    let pos = match tps.parse::<Position>() {
+        Ok(pos) => pos,
+        Err(err) => {
+            eprintln!("Failed to parse TPS: {:?}", err);
+            return false;
+        }
+    };
+
+    let mut eval_file = None;
+
+    let mut i = 1;
+    while i < args.len() {
+        let Some(value) = args.get(i + 1) else {
+            eprintln!("Missing value for '{}'", args[i]);
+            return false;
+        };
+
+        match args[i].as_str() {
+            "evalfile" => eval_file = Some(value),
+            unknown => {
+                eprintln!("Unknown option '{}'", unknown);
+                return false;
+            }
+        }
+
+        i += 2;
+    }
+
+    print_trace(&pos);
+
+    if let Some(eval_file) = eval_file {
+        let network = match Network::load(Path::new(eval_file)) {
+            Ok(network) => network,
+            Err(err) => {
+                eprintln!("Failed to load network '{}': {}", eval_file, err);
+                return false;
+            }
+        };
+
+        if !network.supports(&pos) {
+            eprintln!(
+                "Network '{}' is for size {} and half komi {}",
+                eval_file,
+                network.size(),
+                network.half_komi()
+            );
+            return false;
+        }
 
-    let eval = p1_score - p2_score + flat_position_quality_diff;
+        println!(
+            "Static eval (stm-relative, network): {}",
+            format_flats(network.evaluate(&pos))
+        );
+    }
 
-    eval * pos.stm().sign() + 30
+    true
 }
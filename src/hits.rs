@@ -36,32 +36,47 @@ mod magic;
 pub type Hit = (u8, Square);
 pub type Hits = [Hit; Direction::COUNT];
 
+// the lookup tables cover the full 8x8 grid, so hits past the edge of a smaller board are pulled back onto it
 #[must_use]
-pub fn find_hit_for_dir(blockers: Bitboard, start: Square, dir: Direction) -> Hit {
-    #[cfg(all(feature = "pext", target_feature = "bmi2"))]
-    {
-        return pext::find_hit_for_dir_pext(blockers, start, dir);
+fn clip_to_board(hit: Hit, start: Square, dir: Direction, size: u32) -> Hit {
+    let (dist, _) = hit;
+
+    let max_dist = match dir {
+        Direction::Up => size - 1 - start.rank(),
+        Direction::Down => start.rank(),
+        Direction::Left => start.file(),
+        Direction::Right => size - 1 - start.file(),
+    } as u8;
+
+    if dist <= max_dist {
+        hit
+    } else {
+        let edge_sq = Square::from_raw((start.raw() as i8 + dir.offset() * max_dist as i8) as u8).unwrap();
+        (max_dist, edge_sq)
     }
+}
+
+#[must_use]
+pub fn find_hit_for_dir(blockers: Bitboard, start: Square, dir: Direction, size: u32) -> Hit {
+    #[cfg(all(feature = "pext", target_feature = "bmi2"))]
+    let hit = pext::find_hit_for_dir_pext(blockers, start, dir);
 
     #[cfg(not(all(feature = "pext", target_feature = "bmi2")))]
-    {
-        return magic::find_hit_for_dir_magic(blockers, start, dir);
-    }
+    let hit = magic::find_hit_for_dir_magic(blockers, start, dir);
 
-    unreachable!();
+    clip_to_board(hit, start, dir, size)
 }
 
 #[must_use]
-pub fn find_hits(blockers: Bitboard, start: Square) -> Hits {
+pub fn find_hits(blockers: Bitboard, start: Square, size: u32) -> Hits {
     #[cfg(all(feature = "pext", target_feature = "bmi2"))]
-    {
-        return pext::find_hits_pext(blockers, start);
-    }
+    let hits = pext::find_hits_pext(blockers, start);
 
     #[cfg(not(all(feature = "pext", target_feature = "bmi2")))]
-    {
-        return magic::find_hits_magic(blockers, start);
-    }
+    let hits = magic::find_hits_magic(blockers, start);
 
-    unreachable!();
+    std::array::from_fn(|idx| {
+        let dir = Direction::from_raw(idx as u8).unwrap();
+        clip_to_board(hits[dir.idx()], start, dir, size)
+    })
 }
@@ -23,6 +23,7 @@
 
 use crate::bitboard::Bitboard;
 use crate::core::{Direction, Square};
+#[cfg(target_feature = "bmi2")]
 use std::arch::x86_64::_pdep_u64;
 
 pub(super) const fn generate_mask(sq: Square) -> u64 {
@@ -47,12 +48,13 @@ pub(super) const fn generate_mask(sq: Square) -> u64 {
     mask.raw()
 }
 
+#[cfg(target_feature = "bmi2")]
 pub(super) fn pdep(v: u64, mask: u64) -> u64 {
-    #[cfg(target_feature = "bmi2")]
-    {
-        return unsafe { _pdep_u64(v, mask) };
-    }
+    unsafe { _pdep_u64(v, mask) }
+}
 
+#[cfg(not(target_feature = "bmi2"))]
+pub(super) fn pdep(v: u64, mask: u64) -> u64 {
     let mut mask = mask;
 
     let mut x = 0;
@@ -24,7 +24,7 @@
 use crate::board::Stacks;
 use crate::core::*;
 
-struct Sfc64 {
+pub struct Sfc64 {
     a: u64,
     b: u64,
     c: u64,
@@ -32,7 +32,7 @@ struct Sfc64 {
 }
 
 impl Sfc64 {
-    const fn new(seed: u64) -> Self {
+    pub const fn new(seed: u64) -> Self {
         let mut result = Self {
             a: seed,
             b: seed,
@@ -49,7 +49,7 @@ impl Sfc64 {
         result
     }
 
-    const fn next_u64(&mut self) -> u64 {
+    pub const fn next_u64(&mut self) -> u64 {
         let result = self.a.wrapping_add(self.b).wrapping_add(self.counter);
         self.counter = self.counter.wrapping_add(1);
         self.a = self.b ^ (self.b >> 11);
@@ -89,6 +89,19 @@ const KEYS: [u64; TOTAL_COUNT] = {
     result
 };
 
+// identifies the key set, for anything that persists keys across runs
+pub const KEY_SCHEME_FINGERPRINT: u64 = {
+    let mut result = 0u64;
+
+    let mut idx = 0;
+    while idx < TOTAL_COUNT {
+        result = result.rotate_left(7) ^ KEYS[idx];
+        idx += 1;
+    }
+
+    result
+};
+
 #[must_use]
 pub const fn p2_key() -> u64 {
     KEYS[P2_OFFSET]
@@ -0,0 +1,56 @@
+/*
+ * syntaks, a TEI Tak engine
+ * Copyright (c) 2026 Ciekce
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+pub mod analyse;
+pub mod bench;
+mod bitboard;
+mod board;
+pub mod book;
+mod core;
+mod correction;
+pub mod datagen;
+pub mod eval;
+mod history;
+mod hits;
+mod keys;
+mod limit;
+mod movegen;
+mod movepick;
+mod nnue;
+mod node_counter;
+pub mod packed;
+pub mod perft;
+mod ptn;
+mod road;
+mod search;
+mod symmetry;
+mod takmove;
+pub mod tei;
+pub mod texel;
+mod thread;
+mod tinue;
+mod ttable;
+mod tunable;
+mod util;
+
+pub use board::Position;
@@ -55,6 +55,7 @@ impl TimeManager {
 pub struct Limits {
     start_time: Instant,
     nodes: Option<usize>,
+    soft_nodes: Option<usize>,
     movetime: Option<f64>,
     time_manager: Option<TimeManager>,
 }
@@ -64,6 +65,7 @@ impl Limits {
         Self {
             start_time,
             nodes: None,
+            soft_nodes: None,
             movetime: None,
             time_manager: None,
         }
@@ -79,6 +81,17 @@ impl Limits {
         }
     }
 
+    // only checked between iterations, so the search may overshoot it
+    pub fn set_soft_nodes(&mut self, soft_nodes: usize) -> bool {
+        match self.soft_nodes {
+            None => {
+                self.soft_nodes = Some(soft_nodes);
+                true
+            }
+            Some(_) => false,
+        }
+    }
+
     pub fn set_movetime(&mut self, movetime: f64) -> bool {
         match self.movetime {
             None => {
@@ -107,6 +120,12 @@ impl Limits {
             return true;
         }
 
+        if let Some(soft_nodes) = self.soft_nodes
+            && nodes >= soft_nodes
+        {
+            return true;
+        }
+
         let time = self.start_time.elapsed().as_secs_f64();
 
         if let Some(movetime) = self.movetime
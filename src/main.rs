@@ -21,27 +21,61 @@
  * SOFTWARE.
  */
 
-mod bitboard;
-mod board;
-mod core;
-mod correction;
-mod eval;
-mod history;
-mod hits;
-mod keys;
-mod limit;
-mod movegen;
-mod movepick;
-mod node_counter;
-mod perft;
-mod road;
-mod search;
-mod takmove;
-mod tei;
-mod thread;
-mod ttable;
-mod util;
+use syntaks::{analyse, bench, book, datagen, eval, packed, perft, tei, texel};
 
 fn main() {
-    tei::run();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    match args.first().map(String::as_str) {
+        Some("analyse") => {
+            if !analyse::run(&args[1..]) {
+                std::process::exit(1);
+            }
+        }
+        Some("bench") => {
+            if !bench::run(&args[1..]) {
+                std::process::exit(1);
+            }
+        }
+        Some("datagen") => {
+            if !datagen::run(&args[1..]) {
+                std::process::exit(1);
+            }
+        }
+        Some("eval") => {
+            if !eval::run(&args[1..]) {
+                std::process::exit(1);
+            }
+        }
+        Some("makebook") => {
+            if !book::run_build(&args[1..]) {
+                std::process::exit(1);
+            }
+        }
+        Some("pack") => {
+            if !packed::run_pack(&args[1..]) {
+                std::process::exit(1);
+            }
+        }
+        Some("unpack") => {
+            if !packed::run_unpack(&args[1..]) {
+                std::process::exit(1);
+            }
+        }
+        Some("perft") => {
+            if !perft::run(&args[1..]) {
+                std::process::exit(1);
+            }
+        }
+        Some("tune") => {
+            if !texel::run(&args[1..]) {
+                std::process::exit(1);
+            }
+        }
+        Some(unknown) => {
+            eprintln!("Unknown command '{}'", unknown);
+            std::process::exit(1);
+        }
+        None => tei::run(),
+    }
 }
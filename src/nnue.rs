@@ -0,0 +1,404 @@
+/*
+ * syntaks, a TEI Tak engine
+ * Copyright (c) 2026 Ciekce
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use crate::board::{Position, Stacks};
+use crate::core::{MAX_SIZE, MIN_SIZE, PieceType, Player, Square};
+use crate::search::Score;
+use crate::takmove::Move;
+use crate::util::le::{read_i16s, read_u32};
+use arrayvec::ArrayVec;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+use std::sync::Arc;
+
+pub const HIDDEN_SIZE: usize = 256;
+
+// pieces directly below the top that get their own features
+const CAPTIVE_LAYERS: usize = 3;
+
+// per square: the top piece (owner x type), then the owner of each captive/support layer,
+// with owners given relative to the accumulator's perspective
+const TOP_FEATURES: usize = Player::COUNT * PieceType::COUNT;
+const FEATURES_PER_SQUARE: usize = TOP_FEATURES + CAPTIVE_LAYERS * Player::COUNT;
+
+pub const INPUT_SIZE: usize = Square::COUNT * FEATURES_PER_SQUARE;
+
+const MAX_SQUARE_FEATURES: usize = 1 + CAPTIVE_LAYERS;
+
+// a spread touches its source and at most one square per carried piece
+const MAX_CHANGED_SQUARES: usize = 1 + Move::MAX_CARRY as usize;
+const MAX_CHANGED_FEATURES: usize = MAX_CHANGED_SQUARES * MAX_SQUARE_FEATURES;
+
+const QA: i32 = 255;
+const QB: i32 = 64;
+const SCALE: i32 = 400;
+
+const FILE_MAGIC: [u8; 8] = *b"SYNTAKNN";
+const FILE_VERSION: u32 = 1;
+
+#[derive(Debug)]
+pub enum NetworkError {
+    Io(std::io::Error),
+    InvalidMagic,
+    UnsupportedVersion(u32),
+    ArchitectureMismatch,
+    QuantisationMismatch,
+    InvalidSize(u32),
+    InvalidHalfKomi(u32),
+    TrailingData,
+}
+
+impl From<std::io::Error> for NetworkError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl Display for NetworkError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{}", err),
+            Self::InvalidMagic => write!(f, "not a network file"),
+            Self::UnsupportedVersion(version) => write!(f, "unsupported version {}", version),
+            Self::ArchitectureMismatch => write!(f, "architecture mismatch"),
+            Self::QuantisationMismatch => write!(f, "quantisation mismatch"),
+            Self::InvalidSize(size) => write!(f, "invalid board size {}", size),
+            Self::InvalidHalfKomi(half_komi) => write!(f, "invalid half komi {}", half_komi),
+            Self::TrailingData => write!(f, "trailing data"),
+        }
+    }
+}
+
+// (768 -> 256)x2 -> 1, screlu, trained for a single board size and komi
+pub struct Network {
+    size: u32,
+    half_komi: u32,
+    feature_weights: Box<[[i16; HIDDEN_SIZE]]>,
+    feature_bias: [i16; HIDDEN_SIZE],
+    // side to move's accumulator first
+    output_weights: [[i16; HIDDEN_SIZE]; 2],
+    output_bias: i16,
+}
+
+impl Network {
+    // header: magic, version, input and hidden size, QA, QB, scale, board size, half komi
+    // followed by little-endian i16 feature weights (input-major), feature bias,
+    // output weights (stm then nstm) and output bias
+    pub fn load(path: &Path) -> Result<Self, NetworkError> {
+        let mut reader = BufReader::new(File::open(path)?);
+
+        let mut magic = [0; FILE_MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+
+        if magic != FILE_MAGIC {
+            return Err(NetworkError::InvalidMagic);
+        }
+
+        let version = read_u32(&mut reader)?;
+        if version != FILE_VERSION {
+            return Err(NetworkError::UnsupportedVersion(version));
+        }
+
+        let input_size = read_u32(&mut reader)?;
+        let hidden_size = read_u32(&mut reader)?;
+
+        if input_size as usize != INPUT_SIZE || hidden_size as usize != HIDDEN_SIZE {
+            return Err(NetworkError::ArchitectureMismatch);
+        }
+
+        let qa = read_u32(&mut reader)?;
+        let qb = read_u32(&mut reader)?;
+        let scale = read_u32(&mut reader)?;
+
+        if qa as i32 != QA || qb as i32 != QB || scale as i32 != SCALE {
+            return Err(NetworkError::QuantisationMismatch);
+        }
+
+        let size = read_u32(&mut reader)?;
+        if !(MIN_SIZE..=MAX_SIZE).contains(&size) {
+            return Err(NetworkError::InvalidSize(size));
+        }
+
+        let half_komi = read_u32(&mut reader)?;
+        if half_komi > Position::MAX_HALF_KOMI {
+            return Err(NetworkError::InvalidHalfKomi(half_komi));
+        }
+
+        let mut network = Self {
+            size,
+            half_komi,
+            feature_weights: vec![[0; HIDDEN_SIZE]; INPUT_SIZE].into_boxed_slice(),
+            feature_bias: [0; HIDDEN_SIZE],
+            output_weights: [[0; HIDDEN_SIZE]; 2],
+            output_bias: 0,
+        };
+
+        for weights in network.feature_weights.iter_mut() {
+            read_i16s(&mut reader, weights)?;
+        }
+
+        read_i16s(&mut reader, &mut network.feature_bias)?;
+
+        for weights in network.output_weights.iter_mut() {
+            read_i16s(&mut reader, weights)?;
+        }
+
+        let mut output_bias = [0];
+        read_i16s(&mut reader, &mut output_bias)?;
+        network.output_bias = output_bias[0];
+
+        if reader.read(&mut [0])? != 0 {
+            return Err(NetworkError::TrailingData);
+        }
+
+        Ok(network)
+    }
+
+    #[must_use]
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    #[must_use]
+    pub fn half_komi(&self) -> u32 {
+        self.half_komi
+    }
+
+    #[must_use]
+    pub fn supports(&self, pos: &Position) -> bool {
+        pos.size() == self.size && pos.half_komi() == self.half_komi
+    }
+
+    // full evaluation without an accumulator stack, for one-off evals outside search
+    #[must_use]
+    pub fn evaluate(&self, pos: &Position) -> Score {
+        debug_assert!(self.supports(pos));
+        self.output(&Accumulator::refresh(self, pos), pos.stm())
+    }
+
+    #[must_use]
+    fn output(&self, acc: &Accumulator, stm: Player) -> Score {
+        let screlu = |value: i16| {
+            let value = (value as i32).clamp(0, QA);
+            value * value
+        };
+
+        let mut sum = 0i64;
+
+        for (values, weights) in [acc.values[stm.idx()], acc.values[stm.flip().idx()]]
+            .iter()
+            .zip(self.output_weights.iter())
+        {
+            for (&value, &weight) in values.iter().zip(weights.iter()) {
+                sum += (screlu(value) * weight as i32) as i64;
+            }
+        }
+
+        let output = sum / QA as i64 + self.output_bias as i64;
+        (output * SCALE as i64 / (QA * QB) as i64) as Score
+    }
+}
+
+#[must_use]
+fn square_features(stacks: &Stacks, sq: Square, perspective: Player) -> ArrayVec<usize, MAX_SQUARE_FEATURES> {
+    let mut features = ArrayVec::new();
+
+    let Some(top) = stacks.top(sq) else {
+        return features;
+    };
+
+    let base = sq.idx() * FEATURES_PER_SQUARE;
+
+    let height = stacks.height(sq) as usize;
+    let players = stacks.players(sq);
+
+    // 0 for pieces owned by `perspective`, 1 otherwise
+    let relative_owner = |idx: usize| ((players >> idx) as usize & 1) ^ perspective.idx();
+
+    features.push(base + relative_owner(height - 1) * PieceType::COUNT + top.idx());
+
+    for layer in 0..CAPTIVE_LAYERS.min(height - 1) {
+        let owner = relative_owner(height - 2 - layer);
+        features.push(base + TOP_FEATURES + layer * Player::COUNT + owner);
+    }
+
+    features
+}
+
+#[derive(Clone)]
+#[repr(C, align(64))]
+struct Accumulator {
+    // indexed by perspective
+    values: [[i16; HIDDEN_SIZE]; Player::COUNT],
+}
+
+impl Accumulator {
+    #[must_use]
+    fn refresh(network: &Network, pos: &Position) -> Self {
+        let mut result = Self {
+            values: [network.feature_bias; Player::COUNT],
+        };
+
+        for perspective in [Player::P1, Player::P2] {
+            let values = &mut result.values[perspective.idx()];
+            for sq in pos.occ() {
+                for feature in square_features(pos.stacks(), sq, perspective) {
+                    add_feature(values, &network.feature_weights[feature]);
+                }
+            }
+        }
+
+        result
+    }
+}
+
+fn add_feature(values: &mut [i16; HIDDEN_SIZE], weights: &[i16; HIDDEN_SIZE]) {
+    for (value, &weight) in values.iter_mut().zip(weights.iter()) {
+        *value = value.wrapping_add(weight);
+    }
+}
+
+fn sub_feature(values: &mut [i16; HIDDEN_SIZE], weights: &[i16; HIDDEN_SIZE]) {
+    for (value, &weight) in values.iter_mut().zip(weights.iter()) {
+        *value = value.wrapping_sub(weight);
+    }
+}
+
+#[must_use]
+fn changed_squares(mv: Move) -> ArrayVec<Square, MAX_CHANGED_SQUARES> {
+    let mut squares = ArrayVec::new();
+
+    let mut sq = mv.sq();
+    squares.push(sq);
+
+    if mv.is_spread() {
+        for _ in 0..mv.spread_length() {
+            sq = sq.shift(mv.dir()).unwrap();
+            squares.push(sq);
+        }
+    }
+
+    squares
+}
+
+// per-thread accumulators for each position on the current search path
+pub struct NnueState {
+    network: Option<Arc<Network>>,
+    stack: Vec<Accumulator>,
+}
+
+impl NnueState {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            network: None,
+            stack: Vec::new(),
+        }
+    }
+
+    // the network is only used if it was trained for this position's size and komi
+    pub fn reset(&mut self, network: Option<Arc<Network>>, root_pos: &Position) {
+        self.stack.clear();
+        self.network = network.filter(|network| network.supports(root_pos));
+
+        if let Some(network) = self.network.as_deref() {
+            self.stack.push(Accumulator::refresh(network, root_pos));
+        }
+    }
+
+    pub fn release(&mut self) {
+        self.network = None;
+        self.stack.clear();
+    }
+
+    #[must_use]
+    pub fn is_active(&self) -> bool {
+        self.network.is_some()
+    }
+
+    // `after` must be `before` with `mv` applied
+    pub fn push_move(&mut self, before: &Position, after: &Position, mv: Move) {
+        let Some(network) = self.network.as_deref() else {
+            return;
+        };
+
+        let mut acc = self.stack.last().unwrap().clone();
+
+        for perspective in [Player::P1, Player::P2] {
+            let mut added = ArrayVec::<usize, MAX_CHANGED_FEATURES>::new();
+            let mut removed = ArrayVec::<usize, MAX_CHANGED_FEATURES>::new();
+
+            for sq in changed_squares(mv) {
+                let old = square_features(before.stacks(), sq, perspective);
+                let new = square_features(after.stacks(), sq, perspective);
+
+                added.extend(new.iter().copied().filter(|feature| !old.contains(feature)));
+                removed.extend(old.iter().copied().filter(|feature| !new.contains(feature)));
+            }
+
+            let values = &mut acc.values[perspective.idx()];
+
+            for feature in added {
+                add_feature(values, &network.feature_weights[feature]);
+            }
+
+            for feature in removed {
+                sub_feature(values, &network.feature_weights[feature]);
+            }
+        }
+
+        #[cfg(debug_assertions)]
+        {
+            let expected = Accumulator::refresh(network, after);
+            assert!(acc.values == expected.values, "accumulator mismatch after {}", mv);
+        }
+
+        self.stack.push(acc);
+    }
+
+    pub fn push_nullmove(&mut self) {
+        if self.network.is_none() {
+            return;
+        }
+
+        let acc = self.stack.last().unwrap().clone();
+        self.stack.push(acc);
+    }
+
+    pub fn pop(&mut self) {
+        if self.network.is_none() {
+            return;
+        }
+
+        self.stack.pop();
+        debug_assert!(!self.stack.is_empty());
+    }
+
+    #[must_use]
+    pub fn evaluate(&self, pos: &Position) -> Score {
+        let network = self.network.as_deref().unwrap();
+        network.output(self.stack.last().unwrap(), pos.stm())
+    }
+}
@@ -0,0 +1,382 @@
+/*
+ * syntaks, a TEI Tak engine
+ * Copyright (c) 2026 Ciekce
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use crate::board::{Position, UnpackError};
+use crate::core::Player;
+use crate::ptn::GameResult;
+use crate::search::Score;
+use crate::util::checksum::Checksum;
+use crate::util::le::{read_i16, read_u32, read_u64};
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+
+const FILE_MAGIC: [u8; 8] = *b"SYNTAKPS";
+// bump whenever the record layout or position packing changes
+const FILE_VERSION: u32 = 2;
+
+const HEADER_SIZE: usize = FILE_MAGIC.len() + size_of::<u32>();
+// record count and checksum
+const TRAILER_SIZE: usize = 2 * size_of::<u64>();
+
+const NO_LABEL: u8 = 0;
+const HAS_LABEL: u8 = 1;
+
+// the datagen score and result that may come with a position
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct Label {
+    // P1-relative
+    pub score: Score,
+    pub result: GameResult,
+}
+
+#[derive(Debug)]
+pub(crate) enum PackedFileError {
+    Io(std::io::Error),
+    InvalidMagic,
+    UnsupportedVersion(u32),
+    Truncated,
+    ChecksumMismatch,
+    CountMismatch,
+    InvalidPosition(usize, UnpackError),
+    InvalidLabel(usize),
+}
+
+impl From<std::io::Error> for PackedFileError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl Display for PackedFileError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{}", err),
+            Self::InvalidMagic => write!(f, "not a packed position file"),
+            Self::UnsupportedVersion(version) => write!(f, "unsupported version {}", version),
+            Self::Truncated => write!(f, "truncated"),
+            Self::ChecksumMismatch => write!(f, "checksum mismatch"),
+            Self::CountMismatch => write!(f, "record count mismatch"),
+            Self::InvalidPosition(idx, err) => write!(f, "invalid position in record {}: {:?}", idx, err),
+            Self::InvalidLabel(idx) => write!(f, "invalid label in record {}", idx),
+        }
+    }
+}
+
+#[must_use]
+fn result_code(result: GameResult) -> u8 {
+    match result {
+        GameResult::RoadWin(Player::P1) => 0,
+        GameResult::RoadWin(Player::P2) => 1,
+        GameResult::FlatWin(Player::P1) => 2,
+        GameResult::FlatWin(Player::P2) => 3,
+        GameResult::OtherWin(Player::P1) => 4,
+        GameResult::OtherWin(Player::P2) => 5,
+        GameResult::Draw => 6,
+    }
+}
+
+#[must_use]
+fn result_from_code(code: u8) -> Option<GameResult> {
+    match code {
+        0 => Some(GameResult::RoadWin(Player::P1)),
+        1 => Some(GameResult::RoadWin(Player::P2)),
+        2 => Some(GameResult::FlatWin(Player::P1)),
+        3 => Some(GameResult::FlatWin(Player::P2)),
+        4 => Some(GameResult::OtherWin(Player::P1)),
+        5 => Some(GameResult::OtherWin(Player::P2)),
+        6 => Some(GameResult::Draw),
+        _ => None,
+    }
+}
+
+// header: magic and version
+// each record is a packed position, then a label flag optionally followed by
+// the score as an i16 and a result code. the trailer holds the record count
+// and a checksum over everything before it
+pub(crate) struct PackedWriter<W: Write> {
+    writer: W,
+    checksum: Checksum,
+    count: u64,
+    buf: Vec<u8>,
+}
+
+impl<W: Write> PackedWriter<W> {
+    pub fn new(mut writer: W) -> std::io::Result<Self> {
+        let mut checksum = Checksum::new();
+
+        let mut header = Vec::with_capacity(HEADER_SIZE);
+        header.extend_from_slice(&FILE_MAGIC);
+        header.extend_from_slice(&FILE_VERSION.to_le_bytes());
+
+        checksum.update(&header);
+        writer.write_all(&header)?;
+
+        Ok(Self {
+            writer,
+            checksum,
+            count: 0,
+            buf: Vec::with_capacity(256),
+        })
+    }
+
+    pub fn write(&mut self, pos: &Position, label: Option<Label>) -> std::io::Result<()> {
+        self.buf.clear();
+
+        pos.pack(&mut self.buf);
+
+        match label {
+            Some(label) => {
+                let score = label.score.clamp(i16::MIN as Score, i16::MAX as Score) as i16;
+                self.buf.push(HAS_LABEL);
+                self.buf.extend_from_slice(&score.to_le_bytes());
+                self.buf.push(result_code(label.result));
+            }
+            None => self.buf.push(NO_LABEL),
+        }
+
+        self.checksum.update(&self.buf);
+        self.writer.write_all(&self.buf)?;
+
+        self.count += 1;
+
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> std::io::Result<W> {
+        let count = self.count.to_le_bytes();
+        self.checksum.update(&count);
+
+        self.writer.write_all(&count)?;
+        self.writer.write_all(&self.checksum.value().to_le_bytes())?;
+        self.writer.flush()?;
+
+        Ok(self.writer)
+    }
+}
+
+// the whole file is validated before anything is returned
+pub(crate) fn read_packed(bytes: &[u8]) -> Result<Vec<(Position, Option<Label>)>, PackedFileError> {
+    if bytes.len() < FILE_MAGIC.len() || bytes[..FILE_MAGIC.len()] != FILE_MAGIC {
+        return Err(PackedFileError::InvalidMagic);
+    }
+
+    if bytes.len() < HEADER_SIZE + TRAILER_SIZE {
+        return Err(PackedFileError::Truncated);
+    }
+
+    let version = read_u32(&mut &bytes[FILE_MAGIC.len()..HEADER_SIZE])?;
+    if version != FILE_VERSION {
+        return Err(PackedFileError::UnsupportedVersion(version));
+    }
+
+    let checksum_start = bytes.len() - size_of::<u64>();
+    let count_start = checksum_start - size_of::<u64>();
+
+    let mut checksum = Checksum::new();
+    checksum.update(&bytes[..checksum_start]);
+
+    if read_u64(&mut &bytes[checksum_start..])? != checksum.value() {
+        return Err(PackedFileError::ChecksumMismatch);
+    }
+
+    let count = read_u64(&mut &bytes[count_start..checksum_start])?;
+
+    let records = &bytes[HEADER_SIZE..count_start];
+    let mut offset = 0;
+
+    let mut result = Vec::new();
+
+    while offset < records.len() {
+        let idx = result.len();
+
+        let (pos, len) =
+            Position::unpack(&records[offset..]).map_err(|err| PackedFileError::InvalidPosition(idx, err))?;
+        offset += len;
+
+        let label = match records.get(offset) {
+            Some(&NO_LABEL) => {
+                offset += 1;
+                None
+            }
+            Some(&HAS_LABEL) => {
+                let mut label = records.get(offset + 1..offset + 4).ok_or(PackedFileError::Truncated)?;
+                offset += 4;
+
+                let score = read_i16(&mut label)? as Score;
+                let result = result_from_code(label[0]).ok_or(PackedFileError::InvalidLabel(idx))?;
+
+                Some(Label { score, result })
+            }
+            Some(_) => return Err(PackedFileError::InvalidLabel(idx)),
+            None => return Err(PackedFileError::Truncated),
+        };
+
+        result.push((pos, label));
+    }
+
+    if result.len() as u64 != count {
+        return Err(PackedFileError::CountMismatch);
+    }
+
+    Ok(result)
+}
+
+// a TPS, optionally followed by a datagen score and result
+fn parse_line(line: &str, half_komi: u32) -> Result<(Position, Option<Label>), String> {
+    let parts: Vec<_> = line.split_ascii_whitespace().collect();
+
+    let (tps, label) = match parts.len() {
+        3 => (&parts[..], None),
+        5 => (&parts[..3], Some(&parts[3..])),
+        _ => return Err("expected a TPS, optionally followed by a score and result".to_owned()),
+    };
+
+    let mut pos = Position::from_tps_parts(tps).map_err(|err| format!("invalid TPS: {:?}", err))?;
+    pos.set_half_komi(half_komi);
+
+    let label = match label {
+        Some(label) => {
+            let score = label[0].parse().map_err(|_| format!("invalid score '{}'", label[0]))?;
+            let result = label[1].parse().map_err(|_| format!("invalid result '{}'", label[1]))?;
+            Some(Label { score, result })
+        }
+        None => None,
+    };
+
+    Ok((pos, label))
+}
+
+fn pack_file(input: &str, output: &str, half_komi: u32) -> Result<usize, String> {
+    let input_file = File::open(input).map_err(|err| format!("Failed to open '{}': {}", input, err))?;
+    let output_file = File::create(output).map_err(|err| format!("Failed to create '{}': {}", output, err))?;
+
+    let write_err = |err: std::io::Error| format!("Failed to write '{}': {}", output, err);
+
+    let mut writer = PackedWriter::new(BufWriter::new(output_file)).map_err(write_err)?;
+    let mut count = 0;
+
+    for (line_idx, line) in BufReader::new(input_file).lines().enumerate() {
+        let line = line.map_err(|err| format!("Failed to read '{}': {}", input, err))?;
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (pos, label) = parse_line(line, half_komi).map_err(|err| format!("Line {}: {}", line_idx + 1, err))?;
+
+        writer.write(&pos, label).map_err(write_err)?;
+        count += 1;
+    }
+
+    writer.finish().map_err(write_err)?;
+
+    Ok(count)
+}
+
+// pack <input> <output> [halfkomi <n>]
+pub fn run_pack(args: &[String]) -> bool {
+    let [input, output, rest @ ..] = args else {
+        eprintln!("Missing input or output file");
+        return false;
+    };
+
+    let half_komi = match rest {
+        [] => Position::DEFAULT_HALF_KOMI,
+        [name, value] if name == "halfkomi" => {
+            match value
+                .parse::<u32>()
+                .ok()
+                .filter(|&value| value <= Position::MAX_HALF_KOMI)
+            {
+                Some(half_komi) => half_komi,
+                None => {
+                    eprintln!("Invalid value '{}' for 'halfkomi'", value);
+                    return false;
+                }
+            }
+        }
+        _ => {
+            eprintln!("Unknown options '{}'", rest.join(" "));
+            return false;
+        }
+    };
+
+    match pack_file(input, output, half_komi) {
+        Ok(count) => {
+            println!("Packed {} positions", count);
+            true
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            false
+        }
+    }
+}
+
+fn write_tps(positions: &[(Position, Option<Label>)], output: &str) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(File::create(output)?);
+
+    for (pos, label) in positions.iter() {
+        match label {
+            Some(label) => writeln!(writer, "{} {} {}", pos.tps(), label.score, label.result)?,
+            None => writeln!(writer, "{}", pos.tps())?,
+        }
+    }
+
+    writer.flush()
+}
+
+// unpack <input> <output>
+pub fn run_unpack(args: &[String]) -> bool {
+    let [input, output] = args else {
+        eprintln!("Expected an input and output file");
+        return false;
+    };
+
+    let bytes = match std::fs::read(input) {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            eprintln!("Failed to read '{}': {}", input, err);
+            return false;
+        }
+    };
+
+    let positions = match read_packed(&bytes) {
+        Ok(positions) => positions,
+        Err(err) => {
+            eprintln!("Failed to unpack '{}': {}", input, err);
+            return false;
+        }
+    };
+
+    if let Err(err) = write_tps(&positions, output) {
+        eprintln!("Failed to write '{}': {}", output, err);
+        return false;
+    }
+
+    println!("Unpacked {} positions", positions.len());
+
+    true
+}
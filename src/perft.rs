@@ -21,11 +21,152 @@
  * SOFTWARE.
  */
 
-use crate::board::Position;
+use crate::board::{FlatCountOutcome, Position};
+use crate::core::PieceType;
 use crate::movegen::generate_moves;
+use crate::search::MAX_THREADS;
 use crate::takmove::Move;
+use crate::ttable::MAX_TT_SIZE_MIB;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::thread;
 use std::time::Instant;
 
+#[derive(Copy, Clone, Debug)]
+pub struct PerftOptions {
+    pub threads: usize,
+    // 0 disables the hash table
+    pub hash_mib: usize,
+    // collect per-ply move stats, which visits every leaf and bypasses the hash table
+    pub stats: bool,
+}
+
+impl Default for PerftOptions {
+    fn default() -> Self {
+        Self {
+            threads: 1,
+            hash_mib: 0,
+            stats: false,
+        }
+    }
+}
+
+// counts of the moves made at one ply from the root, by category
+#[derive(Copy, Clone, Debug, Default)]
+pub(crate) struct MoveStats {
+    pub moves: usize,
+    pub flats: usize,
+    pub walls: usize,
+    pub caps: usize,
+    pub spreads: usize,
+    pub crushes: usize,
+    pub road_wins: usize,
+    // moves completing only the opponent's road
+    pub road_losses: usize,
+    // moves ending the game on flats, without a road
+    pub flat_ends: usize,
+}
+
+impl MoveStats {
+    fn add_move(&mut self, pos: &Position, mv: Move, child: &Position) {
+        self.moves += 1;
+
+        if mv.is_spread() {
+            self.spreads += 1;
+            if pos.stacks().top(mv.spread_dest()) == Some(PieceType::Wall) {
+                self.crushes += 1;
+            }
+        } else {
+            match mv.pt() {
+                PieceType::Flat => self.flats += 1,
+                PieceType::Wall => self.walls += 1,
+                PieceType::Capstone => self.caps += 1,
+            }
+        }
+
+        // the mover wins if both roads were completed at once
+        let mover = pos.stm();
+
+        if child.has_road(mover) {
+            self.road_wins += 1;
+        } else if child.has_road(mover.flip()) {
+            self.road_losses += 1;
+        } else if !matches!(child.count_flats(), FlatCountOutcome::None) {
+            self.flat_ends += 1;
+        }
+    }
+
+    fn merge(&mut self, other: &Self) {
+        self.moves += other.moves;
+        self.flats += other.flats;
+        self.walls += other.walls;
+        self.caps += other.caps;
+        self.spreads += other.spreads;
+        self.crushes += other.crushes;
+        self.road_wins += other.road_wins;
+        self.road_losses += other.road_losses;
+        self.flat_ends += other.flat_ends;
+    }
+}
+
+// the key is stored xored with the data, so that torn writes from other threads are rejected
+#[derive(Default)]
+struct PerftEntry {
+    key: AtomicU64,
+    data: AtomicU64,
+}
+
+// always-replace table of subtree counts, keyed by position key and depth
+struct PerftHash {
+    entries: Vec<PerftEntry>,
+}
+
+impl PerftHash {
+    #[must_use]
+    fn new(size_mib: usize) -> Self {
+        let count = size_mib * 1024 * 1024 / size_of::<PerftEntry>();
+
+        let mut entries = Vec::with_capacity(count);
+        entries.resize_with(count, Default::default);
+
+        Self { entries }
+    }
+
+    #[must_use]
+    fn hash_key(pos: &Position, depth: i32) -> u64 {
+        pos.key() ^ (depth as u64).wrapping_mul(0x9e3779b97f4a7c15)
+    }
+
+    #[must_use]
+    fn entry(&self, key: u64) -> &PerftEntry {
+        &self.entries[((key as u128 * self.entries.len() as u128) >> 64) as usize]
+    }
+
+    #[must_use]
+    fn probe(&self, pos: &Position, depth: i32) -> Option<usize> {
+        let key = Self::hash_key(pos, depth);
+        let entry = self.entry(key);
+
+        let data = entry.data.load(Ordering::Relaxed);
+        let stored_key = entry.key.load(Ordering::Relaxed);
+
+        if stored_key ^ data == key && data & 0xff == depth as u64 {
+            Some((data >> 8) as usize)
+        } else {
+            None
+        }
+    }
+
+    fn store(&self, pos: &Position, depth: i32, count: usize) {
+        let key = Self::hash_key(pos, depth);
+        let entry = self.entry(key);
+
+        let data = ((count as u64) << 8) | depth as u64;
+
+        entry.key.store(key ^ data, Ordering::Relaxed);
+        entry.data.store(data, Ordering::Relaxed);
+    }
+}
+
 fn do_perft(pos: &Position, depth: i32, movelists: &mut [Vec<Move>]) -> usize {
     if depth <= 0 {
         return 1;
@@ -50,34 +191,261 @@ fn do_perft(pos: &Position, depth: i32, movelists: &mut [Vec<Move>]) -> usize {
     total
 }
 
-#[must_use]
-pub fn perft(pos: &Position, depth: i32) -> usize {
-    let mut movelists = vec![Vec::with_capacity(256); depth as usize];
-    do_perft(pos, depth.max(1), &mut movelists)
+fn do_perft_hashed(pos: &Position, depth: i32, movelists: &mut [Vec<Move>], hash: &PerftHash) -> usize {
+    if depth <= 0 {
+        return 1;
+    }
+
+    if depth >= 2
+        && let Some(count) = hash.probe(pos, depth)
+    {
+        return count;
+    }
+
+    let (moves, movelists) = movelists.split_first_mut().unwrap();
+    generate_moves(moves, pos);
+
+    if depth == 1 {
+        return moves.len();
+    }
+
+    let mut total = 0;
+
+    for &mut mv in moves {
+        debug_assert!(pos.is_legal(mv));
+
+        let pos = pos.apply_move(mv);
+        total += do_perft_hashed(&pos, depth - 1, movelists, hash);
+    }
+
+    hash.store(pos, depth, total);
+
+    total
 }
 
-pub fn split_perft(pos: &Position, depth: i32) {
-    let depth = depth.max(1);
+// unlike the other variants, this makes every leaf move to classify it
+fn do_perft_stats(pos: &Position, depth: i32, movelists: &mut [Vec<Move>], stats: &mut [MoveStats]) -> usize {
+    if depth <= 0 {
+        return 1;
+    }
+
+    let (moves, movelists) = movelists.split_first_mut().unwrap();
+    generate_moves(moves, pos);
 
-    let mut movelists = vec![Vec::with_capacity(256); depth as usize];
+    let (ply_stats, stats) = stats.split_first_mut().unwrap();
 
-    let start = Instant::now();
+    let mut total = 0;
+
+    for &mut mv in moves {
+        debug_assert!(pos.is_legal(mv));
+
+        let child = pos.apply_move(mv);
+        ply_stats.add_move(pos, mv, &child);
+
+        total += do_perft_stats(&child, depth - 1, movelists, stats);
+    }
+
+    total
+}
+
+fn do_perft_unmake(pos: &mut Position, depth: i32, movelists: &mut [Vec<Move>]) -> usize {
+    if depth <= 0 {
+        return 1;
+    }
 
     let (moves, movelists) = movelists.split_first_mut().unwrap();
     generate_moves(moves, pos);
 
+    if depth == 1 {
+        return moves.len();
+    }
+
     let mut total = 0;
 
     for &mut mv in moves {
         debug_assert!(pos.is_legal(mv));
 
-        print!("{:9}  ", mv.to_string());
+        let undo = pos.make_move(mv);
+        total += do_perft_unmake(pos, depth - 1, movelists);
+        pos.unmake_move(mv, &undo);
+    }
 
-        let pos = pos.apply_move(mv);
-        let value = do_perft(&pos, depth - 1, movelists);
+    total
+}
+
+#[must_use]
+pub fn perft(pos: &Position, depth: i32) -> usize {
+    let mut movelists = vec![Vec::with_capacity(256); depth.max(0) as usize];
+    do_perft(pos, depth, &mut movelists)
+}
+
+#[must_use]
+pub fn perft_unmake(pos: &Position, depth: i32) -> usize {
+    let mut movelists = vec![Vec::with_capacity(256); depth.max(0) as usize];
+    let mut pos = *pos;
+    do_perft_unmake(&mut pos, depth, &mut movelists)
+}
+
+// counts the subtree under each root move, with root moves handed out to the threads in order,
+// along with the per-ply move stats if requested
+#[must_use]
+fn root_move_counts(pos: &Position, depth: i32, options: &PerftOptions) -> (Vec<(Move, usize)>, Vec<MoveStats>) {
+    let depth = depth.max(1);
+
+    let mut moves = Vec::with_capacity(256);
+    generate_moves(&mut moves, pos);
+
+    let hash = (options.hash_mib > 0 && !options.stats).then(|| PerftHash::new(options.hash_mib));
+    let next = AtomicUsize::new(0);
+
+    let mut counts = vec![0; moves.len()];
+    let stats_plies = if options.stats { depth as usize } else { 0 };
+    let mut stats = vec![MoveStats::default(); stats_plies];
+
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..options.threads.max(1))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut movelists = vec![Vec::with_capacity(256); depth as usize - 1];
+                    let mut results = Vec::new();
+                    let mut thread_stats = vec![MoveStats::default(); stats_plies];
+
+                    loop {
+                        let idx = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(&mv) = moves.get(idx) else {
+                            break;
+                        };
+
+                        debug_assert!(pos.is_legal(mv));
+
+                        let child = pos.apply_move(mv);
+
+                        let count = if let Some((root_stats, stats)) = thread_stats.split_first_mut() {
+                            root_stats.add_move(pos, mv, &child);
+                            do_perft_stats(&child, depth - 1, &mut movelists, stats)
+                        } else if let Some(hash) = &hash {
+                            do_perft_hashed(&child, depth - 1, &mut movelists, hash)
+                        } else {
+                            do_perft(&child, depth - 1, &mut movelists)
+                        };
+
+                        results.push((idx, count));
+                    }
+
+                    (results, thread_stats)
+                })
+            })
+            .collect();
+
+        for worker in workers {
+            let (results, thread_stats) = worker.join().unwrap();
+
+            for (idx, count) in results {
+                counts[idx] = count;
+            }
+
+            for (ply_stats, thread_ply_stats) in stats.iter_mut().zip(&thread_stats) {
+                ply_stats.merge(thread_ply_stats);
+            }
+        }
+    });
+
+    (moves.into_iter().zip(counts).collect(), stats)
+}
+
+fn print_stats(stats: &[MoveStats]) {
+    println!();
+    println!(
+        "{:>3} {:>14} {:>14} {:>12} {:>12} {:>14} {:>12} {:>10} {:>11} {:>10}",
+        "ply", "moves", "flats", "walls", "caps", "spreads", "crushes", "road wins", "road losses", "flat ends"
+    );
+
+    for (ply, stats) in stats.iter().enumerate() {
+        println!(
+            "{:>3} {:>14} {:>14} {:>12} {:>12} {:>14} {:>12} {:>10} {:>11} {:>10}",
+            ply + 1,
+            stats.moves,
+            stats.flats,
+            stats.walls,
+            stats.caps,
+            stats.spreads,
+            stats.crushes,
+            stats.road_wins,
+            stats.road_losses,
+            stats.flat_ends
+        );
+    }
+}
+
+#[must_use]
+pub fn perft_with_options(pos: &Position, depth: i32, options: &PerftOptions) -> usize {
+    // only the root itself
+    if depth <= 0 {
+        return 1;
+    }
+
+    let (counts, _) = root_move_counts(pos, depth, options);
+    counts.iter().map(|&(_, count)| count).sum()
+}
+
+// prints the node count, followed by the move stats if requested
+pub(crate) fn print_perft(pos: &Position, depth: i32, options: &PerftOptions) {
+    if depth <= 0 {
+        println!("1");
+        return;
+    }
+
+    let (counts, stats) = root_move_counts(pos, depth, options);
+
+    println!("{}", counts.iter().map(|&(_, count)| count).sum::<usize>());
+
+    if options.stats {
+        print_stats(&stats);
+    }
+}
+
+// runs perft with both copy-make and make/unmake, to check that they agree and compare speed
+pub(crate) fn compare_perft(pos: &Position, depth: i32) -> bool {
+    let start = Instant::now();
+    let copy_nodes = perft(pos, depth);
+    let copy_time = start.elapsed().as_secs_f64();
+
+    let start = Instant::now();
+    let unmake_nodes = perft_unmake(pos, depth);
+    let unmake_time = start.elapsed().as_secs_f64();
+
+    println!(
+        "copy-make:   {} nodes {:.3}s {} nps",
+        copy_nodes,
+        copy_time,
+        (copy_nodes as f64 / copy_time) as usize
+    );
+    println!(
+        "make/unmake: {} nodes {:.3}s {} nps",
+        unmake_nodes,
+        unmake_time,
+        (unmake_nodes as f64 / unmake_time) as usize
+    );
+
+    if copy_nodes == unmake_nodes {
+        println!("node counts match");
+        true
+    } else {
+        println!("node counts differ");
+        false
+    }
+}
+
+pub(crate) fn split_perft(pos: &Position, depth: i32, options: &PerftOptions) {
+    let start = Instant::now();
+
+    let (counts, stats) = root_move_counts(pos, depth, options);
+
+    let mut total = 0;
 
-        total += value;
-        println!("{}", value);
+    for (mv, count) in counts {
+        println!("{:9}  {}", mv.to_string(), count);
+        total += count;
     }
 
     let nps = (total as f64 / start.elapsed().as_secs_f64()) as usize;
@@ -85,4 +453,94 @@ pub fn split_perft(pos: &Position, depth: i32) {
     println!();
     println!("total: {}", total);
     println!("{} nps", nps);
+
+    if options.stats {
+        print_stats(&stats);
+    }
+}
+
+// [threads <n>] [hash <mib>] [stats]
+#[must_use]
+pub(crate) fn parse_options(args: &[&str]) -> Option<PerftOptions> {
+    let mut options = PerftOptions::default();
+
+    let mut i = 0;
+    while i < args.len() {
+        if args[i] == "stats" {
+            options.stats = true;
+            i += 1;
+            continue;
+        }
+
+        let Some(value) = args.get(i + 1) else {
+            eprintln!("Missing value for '{}'", args[i]);
+            return None;
+        };
+
+        let valid = match args[i] {
+            "threads" => value
+                .parse::<usize>()
+                .ok()
+                .filter(|value| (1..=MAX_THREADS as usize).contains(value))
+                .map(|value| options.threads = value)
+                .is_some(),
+            "hash" => value
+                .parse::<usize>()
+                .ok()
+                .filter(|&value| value <= MAX_TT_SIZE_MIB)
+                .map(|value| options.hash_mib = value)
+                .is_some(),
+            unknown => {
+                eprintln!("Unknown option '{}'", unknown);
+                return None;
+            }
+        };
+
+        if !valid {
+            eprintln!("Invalid value '{}' for '{}'", value, args[i]);
+            return None;
+        }
+
+        i += 2;
+    }
+
+    Some(options)
+}
+
+// perft <tps> <depth> [threads <n>] [hash <mib>] [stats]
+pub fn run(args: &[String]) -> bool {
+    let Some(tps) = args.first() else {
+        eprintln!("Missing TPS");
+        return false;
+    };
+
+    let Some(depth) = args.get(1) else {
+        eprintln!("Missing depth");
+        return false;
+    };
+
+    let pos = match tps.parse::<Position>() {
+        Ok(pos) => pos,
+        Err(err) => {
+            eprintln!("Failed to parse TPS: {:?}", err);
+            return false;
+        }
+    };
+
+    let depth = match depth.parse() {
+        Ok(depth) => depth,
+        Err(_) => {
+            eprintln!("Invalid depth '{}'", depth);
+            return false;
+        }
+    };
+
+    let options: Vec<_> = args[2..].iter().map(String::as_str).collect();
+    let Some(options) = parse_options(&options) else {
+        return false;
+    };
+
+    print_perft(&pos, depth, &options);
+
+    true
 }
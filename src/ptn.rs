@@ -0,0 +1,425 @@
+/*
+ * syntaks, a TEI Tak engine
+ * Copyright (c) 2026 Ciekce
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use crate::board::{FlatCountOutcome, Position, TpsError};
+use crate::core::{MAX_SIZE, MIN_SIZE, Player};
+use crate::takmove::{Move, MoveStrError};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum GameResult {
+    RoadWin(Player),
+    FlatWin(Player),
+    OtherWin(Player),
+    Draw,
+}
+
+impl Display for GameResult {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            GameResult::RoadWin(Player::P1) => "R-0",
+            GameResult::RoadWin(Player::P2) => "0-R",
+            GameResult::FlatWin(Player::P1) => "F-0",
+            GameResult::FlatWin(Player::P2) => "0-F",
+            GameResult::OtherWin(Player::P1) => "1-0",
+            GameResult::OtherWin(Player::P2) => "0-1",
+            GameResult::Draw => "1/2-1/2",
+        };
+        f.write_str(s)
+    }
+}
+
+impl GameResult {
+    // the result of a game that has ended in `pos`, or None if it is still going
+    #[must_use]
+    pub fn from_position(pos: &Position) -> Option<Self> {
+        // the player who just moved wins if both roads were completed at once
+        let mover = pos.stm().flip();
+
+        for player in [mover, mover.flip()] {
+            if pos.road(player).is_some() {
+                return Some(GameResult::RoadWin(player));
+            }
+        }
+
+        match pos.count_flats() {
+            FlatCountOutcome::None => None,
+            FlatCountOutcome::Draw => Some(GameResult::Draw),
+            FlatCountOutcome::Win(player) => Some(GameResult::FlatWin(player)),
+        }
+    }
+}
+
+impl FromStr for GameResult {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "R-0" => Ok(GameResult::RoadWin(Player::P1)),
+            "0-R" => Ok(GameResult::RoadWin(Player::P2)),
+            "F-0" => Ok(GameResult::FlatWin(Player::P1)),
+            "0-F" => Ok(GameResult::FlatWin(Player::P2)),
+            "1-0" => Ok(GameResult::OtherWin(Player::P1)),
+            "0-1" => Ok(GameResult::OtherWin(Player::P2)),
+            "1/2-1/2" => Ok(GameResult::Draw),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum PtnError {
+    UnterminatedTag,
+    UnterminatedComment,
+    InvalidTag(String),
+    InvalidSize(String),
+    InvalidKomi(String),
+    InvalidTps(TpsError),
+    SizeMismatch,
+    InvalidMove(String, MoveStrError),
+    IllegalMove(String),
+    MovesAfterResult,
+}
+
+#[derive(Clone, Debug)]
+pub struct PtnGame {
+    tags: Vec<(String, String)>,
+    start_pos: Position,
+    final_pos: Position,
+    moves: Vec<Move>,
+    result: Option<GameResult>,
+}
+
+impl PtnGame {
+    // the start position determines the Size, Komi and (if needed) TPS tags
+    #[must_use]
+    pub fn new(start_pos: Position) -> Self {
+        let mut game = Self {
+            tags: Vec::new(),
+            start_pos,
+            final_pos: start_pos,
+            moves: Vec::new(),
+            result: None,
+        };
+
+        game.set_tag("Size", &start_pos.size().to_string());
+        game.set_tag("Komi", &format_komi(start_pos.half_komi()));
+
+        let mut initial = Position::startpos(start_pos.size());
+        initial.set_half_komi(start_pos.half_komi());
+
+        if start_pos != initial {
+            game.set_tag("TPS", &start_pos.tps());
+        }
+
+        game
+    }
+
+    #[must_use]
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(tag, _)| tag.eq_ignore_ascii_case(name)) {
+            Some((_, existing)) => *existing = value.to_owned(),
+            None => self.tags.push((name.to_owned(), value.to_owned())),
+        }
+    }
+
+    #[must_use]
+    pub fn start_pos(&self) -> &Position {
+        &self.start_pos
+    }
+
+    #[must_use]
+    pub fn moves(&self) -> &[Move] {
+        &self.moves
+    }
+
+    #[must_use]
+    pub fn result(&self) -> Option<GameResult> {
+        self.result
+    }
+
+    pub fn set_result(&mut self, result: Option<GameResult>) {
+        self.result = result;
+        match result {
+            Some(result) => self.set_tag("Result", &result.to_string()),
+            None => self.tags.retain(|(tag, _)| !tag.eq_ignore_ascii_case("Result")),
+        }
+    }
+
+    // also records the result if this move ends the game
+    pub fn push_move(&mut self, mv: Move) {
+        debug_assert!(self.result.is_none());
+        debug_assert!(self.final_pos.is_legal(mv));
+
+        self.moves.push(mv);
+        self.final_pos = self.final_pos.apply_move(mv);
+
+        if let Some(result) = GameResult::from_position(&self.final_pos) {
+            self.set_result(Some(result));
+        }
+    }
+
+    #[must_use]
+    pub fn final_pos(&self) -> &Position {
+        &self.final_pos
+    }
+}
+
+#[must_use]
+fn format_komi(half_komi: u32) -> String {
+    if half_komi.is_multiple_of(2) {
+        format!("{}", half_komi / 2)
+    } else {
+        format!("{}.5", half_komi / 2)
+    }
+}
+
+fn parse_half_komi(komi: &str) -> Result<u32, PtnError> {
+    let err = || PtnError::InvalidKomi(komi.to_owned());
+
+    let (whole, half) = match komi.split_once('.') {
+        Some((whole, "5")) => (whole, 1),
+        Some((whole, frac)) if frac.bytes().all(|c| c == b'0') => (whole, 0),
+        Some(_) => return Err(err()),
+        None => (komi, 0),
+    };
+
+    let half_komi = whole.parse::<u32>().map_err(|_| err())? * 2 + half;
+
+    if half_komi > Position::MAX_HALF_KOMI {
+        return Err(err());
+    }
+
+    Ok(half_komi)
+}
+
+fn parse_tag(tag: &str) -> Result<(String, String), PtnError> {
+    let (name, value) = tag
+        .trim()
+        .split_once(char::is_whitespace)
+        .ok_or_else(|| PtnError::InvalidTag(tag.to_owned()))?;
+
+    let value = value
+        .trim()
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .ok_or_else(|| PtnError::InvalidTag(tag.to_owned()))?;
+
+    Ok((name.to_owned(), value.to_owned()))
+}
+
+impl FromStr for PtnGame {
+    type Err = PtnError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut tags = Vec::new();
+        let mut tokens = Vec::new();
+
+        let mut remaining = s.trim_start();
+
+        while !remaining.is_empty() {
+            if let Some(rest) = remaining.strip_prefix('[') {
+                let end = rest.find(']').ok_or(PtnError::UnterminatedTag)?;
+                tags.push(parse_tag(&rest[..end])?);
+                remaining = &rest[(end + 1)..];
+            } else if let Some(rest) = remaining.strip_prefix('{') {
+                let end = rest.find('}').ok_or(PtnError::UnterminatedComment)?;
+                remaining = &rest[(end + 1)..];
+            } else {
+                let end = remaining
+                    .find(|c: char| c.is_whitespace() || c == '[' || c == '{')
+                    .unwrap_or(remaining.len());
+                tokens.push(&remaining[..end]);
+                remaining = &remaining[end..];
+            }
+
+            remaining = remaining.trim_start();
+        }
+
+        let mut game = Self {
+            tags,
+            start_pos: Position::startpos(Position::DEFAULT_SIZE),
+            final_pos: Position::startpos(Position::DEFAULT_SIZE),
+            moves: Vec::new(),
+            result: None,
+        };
+
+        let size = match game.tag("Size") {
+            Some(size) => match size.parse::<u32>() {
+                Ok(size) if (MIN_SIZE..=MAX_SIZE).contains(&size) => Some(size),
+                _ => return Err(PtnError::InvalidSize(size.to_owned())),
+            },
+            None => None,
+        };
+
+        let mut pos = match game.tag("TPS") {
+            Some(tps) => {
+                let pos = tps.parse::<Position>().map_err(PtnError::InvalidTps)?;
+                if size.is_some_and(|size| size != pos.size()) {
+                    return Err(PtnError::SizeMismatch);
+                }
+                pos
+            }
+            None => Position::startpos(size.unwrap_or(Position::DEFAULT_SIZE)),
+        };
+
+        // a missing Komi tag means no komi, not the engine's default
+        let half_komi = match game.tag("Komi") {
+            Some(komi) => parse_half_komi(komi)?,
+            None => 0,
+        };
+        pos.set_half_komi(half_komi);
+
+        game.start_pos = pos;
+        game.final_pos = pos;
+
+        for token in tokens {
+            if let Ok(result) = token.parse::<GameResult>() {
+                game.result = Some(result);
+                continue;
+            }
+
+            // "0-0" marks an unfinished game
+            if token == "0-0" {
+                continue;
+            }
+
+            if game.result.is_some() {
+                return Err(PtnError::MovesAfterResult);
+            }
+
+            // move numbers, and placeholders for moves before a TPS start position
+            if token == "--"
+                || token
+                    .strip_suffix('.')
+                    .is_some_and(|n| n.bytes().all(|c| c.is_ascii_digit()))
+            {
+                continue;
+            }
+
+            // tak and move quality annotations
+            let move_str = token.trim_end_matches(['\'', '"', '!', '?']);
+
+            let mv = move_str
+                .parse::<Move>()
+                .map_err(|err| PtnError::InvalidMove(token.to_owned(), err))?;
+
+            if !game.final_pos.is_legal(mv) {
+                return Err(PtnError::IllegalMove(token.to_owned()));
+            }
+
+            game.moves.push(mv);
+            game.final_pos = game.final_pos.apply_move(mv);
+        }
+
+        if game.result.is_none()
+            && let Some(result) = game.tag("Result").and_then(|result| result.parse().ok())
+        {
+            game.result = Some(result);
+        }
+
+        if game.result.is_none() {
+            game.result = GameResult::from_position(&game.final_pos);
+        }
+
+        Ok(game)
+    }
+}
+
+// splits a collection into its games, each of which starts with its tags
+#[must_use]
+pub fn split_games(s: &str) -> Vec<&str> {
+    let mut games = Vec::new();
+
+    let mut start = 0;
+    let mut offset = 0;
+    let mut seen_moves = false;
+
+    for line in s.split_inclusive('\n') {
+        let trimmed = line.trim_start();
+
+        if trimmed.starts_with('[') {
+            if seen_moves {
+                games.push(&s[start..offset]);
+                start = offset;
+                seen_moves = false;
+            }
+        } else if !trimmed.is_empty() {
+            seen_moves = true;
+        }
+
+        offset += line.len();
+    }
+
+    if !s[start..].trim().is_empty() {
+        games.push(&s[start..]);
+    }
+
+    games
+}
+
+impl Display for PtnGame {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (name, value) in self.tags.iter() {
+            writeln!(f, "[{} \"{}\"]", name, value)?;
+        }
+
+        writeln!(f)?;
+
+        let mut pos = self.start_pos;
+        let mut moves = self.moves.iter();
+
+        if pos.stm() == Player::P2
+            && let Some(&mv) = moves.next()
+        {
+            writeln!(f, "{}. -- {}", pos.ply() / 2 + 1, mv)?;
+            pos = pos.apply_move(mv);
+        }
+
+        while let Some(&mv) = moves.next() {
+            write!(f, "{}. {}", pos.ply() / 2 + 1, mv)?;
+            pos = pos.apply_move(mv);
+
+            if let Some(&mv) = moves.next() {
+                write!(f, " {}", mv)?;
+                pos = pos.apply_move(mv);
+            }
+
+            writeln!(f)?;
+        }
+
+        if let Some(result) = self.result {
+            writeln!(f, "{}", result)?;
+        }
+
+        Ok(())
+    }
+}
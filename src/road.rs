@@ -28,13 +28,17 @@ mod avx2;
 mod sse;
 
 use crate::bitboard::Bitboard;
+use crate::core::{Direction, Square};
+use std::fmt::{Display, Formatter};
 
 #[must_use]
-pub fn has_road(road_occ: Bitboard) -> bool {
-    let upper_edge = Bitboard::UPPER_EDGE.raw();
-    let lower_edge = Bitboard::LOWER_EDGE.raw();
-    let left_edge = Bitboard::LEFT_EDGE.raw();
-    let right_edge = Bitboard::RIGHT_EDGE.raw();
+pub fn has_road(road_occ: Bitboard, size: u32) -> bool {
+    const STRIDE: u32 = Square::STRIDE;
+
+    let upper_edge = Bitboard::board_edge(size, Direction::Up).raw();
+    let lower_edge = Bitboard::board_edge(size, Direction::Down).raw();
+    let left_edge = Bitboard::board_edge(size, Direction::Left).raw();
+    let right_edge = Bitboard::board_edge(size, Direction::Right).raw();
 
     let road_occ = road_occ.raw();
 
@@ -43,21 +47,183 @@ pub fn has_road(road_occ: Bitboard) -> bool {
     let left = road_occ & left_edge;
     let right = road_occ & right_edge;
 
-    let up = up | (up >> 6 & road_occ);
-    let down = down | (down << 6 & road_occ);
+    let up = up | (up >> STRIDE & road_occ);
+    let down = down | (down << STRIDE & road_occ);
     let left = left | (left << 1 & road_occ);
     let right = right | (right >> 1 & road_occ);
 
     #[cfg(target_feature = "avx2")]
     {
         //SAFETY: self-explanatory
-        return unsafe { avx2::has_road(road_occ, up, down, left, right) };
+        unsafe { avx2::has_road(road_occ, up, down, left, right) }
     }
 
     #[cfg(all(not(target_feature = "avx2"), target_feature = "sse4.2"))]
     {
-        return unsafe { sse::has_road(road_occ, up, down, left, right) };
+        unsafe { sse::has_road(road_occ, up, down, left, right) }
+    }
+
+    #[cfg(not(any(target_feature = "avx2", target_feature = "sse4.2")))]
+    {
+        let road_occ = Bitboard::from_raw(road_occ);
+        flood_fill(road_occ, up) & down != 0 || flood_fill(road_occ, left) & right != 0
+    }
+}
+
+// squares connected to each edge of the board through `road_occ`, indexed by direction
+#[must_use]
+pub fn edge_regions(road_occ: Bitboard, size: u32) -> [Bitboard; Direction::COUNT] {
+    let road_occ = road_occ.raw();
+
+    let up = road_occ & Bitboard::board_edge(size, Direction::Up).raw();
+    let down = road_occ & Bitboard::board_edge(size, Direction::Down).raw();
+    let left = road_occ & Bitboard::board_edge(size, Direction::Left).raw();
+    let right = road_occ & Bitboard::board_edge(size, Direction::Right).raw();
+
+    #[cfg(target_feature = "avx2")]
+    let regions = unsafe { avx2::edge_regions(road_occ, up, down, left, right) };
+
+    #[cfg(all(not(target_feature = "avx2"), target_feature = "sse4.2"))]
+    let regions = unsafe { sse::edge_regions(road_occ, up, down, left, right) };
+
+    #[cfg(not(any(target_feature = "avx2", target_feature = "sse4.2")))]
+    let regions = [up, down, left, right].map(|seed| flood_fill(Bitboard::from_raw(road_occ), seed));
+
+    regions.map(Bitboard::from_raw)
+}
+
+// grows `seed` through neighbouring squares of `road_occ` until it stops changing
+#[cfg(not(any(target_feature = "avx2", target_feature = "sse4.2")))]
+#[must_use]
+fn flood_fill(road_occ: Bitboard, seed: u64) -> u64 {
+    let mut region = Bitboard::from_raw(seed);
+
+    loop {
+        let mut next = region;
+        for dir in [Direction::Up, Direction::Down, Direction::Left, Direction::Right] {
+            next |= region.shift(dir) & road_occ;
+        }
+
+        if next == region {
+            return region.raw();
+        }
+
+        region = next;
     }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum RoadOrientation {
+    // connects the left and right edges
+    Horizontal,
+    // connects the upper and lower edges
+    Vertical,
+    Both,
+}
+
+impl Display for RoadOrientation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            RoadOrientation::Horizontal => "horizontal",
+            RoadOrientation::Vertical => "vertical",
+            RoadOrientation::Both => "horizontal and vertical",
+        };
+        f.write_str(s)
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Road {
+    // a shortest edge-to-edge path through the road, without any branches hanging off it,
+    // or the union of one such path in each direction if the road runs both ways
+    pub squares: Bitboard,
+    pub orientation: RoadOrientation,
+}
+
+#[must_use]
+pub fn find_road(road_occ: Bitboard, size: u32) -> Option<Road> {
+    let regions = edge_regions(road_occ, size);
+
+    let vertical = regions[Direction::Up.idx()] & regions[Direction::Down.idx()];
+    let horizontal = regions[Direction::Left.idx()] & regions[Direction::Right.idx()];
+
+    let orientation = match (horizontal.is_empty(), vertical.is_empty()) {
+        (true, true) => return None,
+        (false, true) => RoadOrientation::Horizontal,
+        (true, false) => RoadOrientation::Vertical,
+        (false, false) => RoadOrientation::Both,
+    };
+
+    let mut squares = Bitboard::empty();
+
+    if !horizontal.is_empty() {
+        squares |= shortest_path(horizontal, size, Direction::Left, Direction::Right);
+    }
+
+    if !vertical.is_empty() {
+        squares |= shortest_path(vertical, size, Direction::Down, Direction::Up);
+    }
+
+    Some(Road { squares, orientation })
+}
+
+// bfs through `component`, which must connect both edges, keeping parent links
+// so the path can be walked back from whichever square reaches `to` first
+fn shortest_path(component: Bitboard, size: u32, from: Direction, to: Direction) -> Bitboard {
+    let target = component & Bitboard::board_edge(size, to);
+
+    let mut parents = [None::<Square>; Square::COUNT];
+    let mut visited = component & Bitboard::board_edge(size, from);
+    let mut frontier = visited;
+
+    loop {
+        let reached = frontier & target;
+        if let Some(mut sq) = reached.lsb() {
+            let mut path = sq.bb();
+            while let Some(parent) = parents[sq.idx()] {
+                path.set_sq(parent);
+                sq = parent;
+            }
+            return path;
+        }
+
+        debug_assert!(!frontier.is_empty());
+
+        let mut next = Bitboard::empty();
+
+        for sq in frontier {
+            for dir in [Direction::Up, Direction::Down, Direction::Left, Direction::Right] {
+                let Some(neighbor) = sq.shift_checked(dir) else {
+                    continue;
+                };
+
+                if component.has_sq(neighbor) && !visited.has_sq(neighbor) {
+                    visited.set_sq(neighbor);
+                    next.set_sq(neighbor);
+                    parents[neighbor.idx()] = Some(sq);
+                }
+            }
+        }
+
+        frontier = next;
+    }
+}
+
+// empty squares that would complete a road if a road piece were placed on them
+#[must_use]
+pub fn placement_threats(road_occ: Bitboard, empty: Bitboard, size: u32) -> Bitboard {
+    let regions = edge_regions(road_occ, size);
+
+    let reaches = |dir: Direction| {
+        let region = regions[dir.idx()];
+        let adjacent = [Direction::Up, Direction::Down, Direction::Left, Direction::Right]
+            .into_iter()
+            .fold(Bitboard::empty(), |adjacent, dir| adjacent | region.shift(dir));
+        adjacent | Bitboard::board_edge(size, dir)
+    };
+
+    let vertical = reaches(Direction::Up) & reaches(Direction::Down);
+    let horizontal = reaches(Direction::Left) & reaches(Direction::Right);
 
-    todo!();
+    (vertical | horizontal) & empty
 }
@@ -22,6 +22,7 @@
  */
 
 use crate::bitboard::Bitboard;
+use crate::core::Square;
 use std::arch::x86_64::*;
 
 #[must_use]
@@ -40,8 +41,8 @@ pub(super) fn has_road(road_occ: u64, up: u64, down: u64, left: u64, right: u64)
     let road_occ = _mm256_set1_epi64x(road_occ as i64);
 
     let calc_next_masks = |masks| {
-        let next_masks_u = _mm256_slli_epi64::<6>(masks);
-        let next_masks_d = _mm256_srli_epi64::<6>(masks);
+        let next_masks_u = _mm256_slli_epi64::<{ Square::STRIDE as i32 }>(masks);
+        let next_masks_d = _mm256_srli_epi64::<{ Square::STRIDE as i32 }>(masks);
         let next_masks_ud = _mm256_or_si256(next_masks_u, next_masks_d);
 
         let next_masks_l = _mm256_andnot_si256(left_edge, _mm256_slli_epi64::<1>(masks));
@@ -85,3 +86,38 @@ pub(super) fn has_road(road_occ: u64, up: u64, down: u64, left: u64, right: u64)
         masks = next_masks;
     }
 }
+
+#[must_use]
+#[target_feature(enable = "avx2")]
+pub(super) fn edge_regions(road_occ: u64, up: u64, down: u64, left: u64, right: u64) -> [u64; 4] {
+    let mut masks = _mm256_set_epi64x(up as i64, down as i64, left as i64, right as i64);
+
+    let left_edge = _mm256_set1_epi64x(Bitboard::LEFT_EDGE.raw() as i64);
+    let right_edge = _mm256_set1_epi64x(Bitboard::RIGHT_EDGE.raw() as i64);
+
+    let road_occ = _mm256_set1_epi64x(road_occ as i64);
+
+    loop {
+        let next_masks_u = _mm256_slli_epi64::<{ Square::STRIDE as i32 }>(masks);
+        let next_masks_d = _mm256_srli_epi64::<{ Square::STRIDE as i32 }>(masks);
+        let next_masks_ud = _mm256_or_si256(next_masks_u, next_masks_d);
+
+        let next_masks_l = _mm256_andnot_si256(left_edge, _mm256_slli_epi64::<1>(masks));
+        let next_masks_r = _mm256_andnot_si256(right_edge, _mm256_srli_epi64::<1>(masks));
+        let next_masks_lr = _mm256_or_si256(next_masks_l, next_masks_r);
+
+        let next_masks = _mm256_or_si256(next_masks_ud, next_masks_lr);
+        let next_masks = _mm256_or_si256(masks, _mm256_and_si256(next_masks, road_occ));
+
+        let unchanged = _mm256_cmpeq_epi64(next_masks, masks);
+
+        masks = next_masks;
+
+        if _mm256_movemask_epi8(unchanged) == -1 {
+            break;
+        }
+    }
+
+    let [right, left, down, up] = unsafe { std::mem::transmute::<__m256i, [u64; 4]>(masks) };
+    [up, down, left, right]
+}
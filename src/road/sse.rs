@@ -22,6 +22,7 @@
  */
 
 use crate::bitboard::Bitboard;
+use crate::core::Square;
 use std::arch::x86_64::*;
 
 #[must_use]
@@ -36,8 +37,8 @@ pub(super) fn has_road(road_occ: u64, up: u64, down: u64, left: u64, right: u64)
     let road_occ = _mm_set1_epi64x(road_occ as i64);
 
     let calc_next_masks = |masks| {
-        let next_masks_u = _mm_slli_epi64::<6>(masks);
-        let next_masks_d = _mm_srli_epi64::<6>(masks);
+        let next_masks_u = _mm_slli_epi64::<{ Square::STRIDE as i32 }>(masks);
+        let next_masks_d = _mm_srli_epi64::<{ Square::STRIDE as i32 }>(masks);
         let next_masks_ud = _mm_or_si128(next_masks_u, next_masks_d);
 
         let next_masks_l = _mm_andnot_si128(left_edge, _mm_slli_epi64::<1>(masks));
@@ -71,3 +72,49 @@ pub(super) fn has_road(road_occ: u64, up: u64, down: u64, left: u64, right: u64)
         masks_dr = next_masks_dr;
     }
 }
+
+#[must_use]
+#[target_feature(enable = "sse4.2")]
+pub(super) fn edge_regions(road_occ: u64, up: u64, down: u64, left: u64, right: u64) -> [u64; 4] {
+    let mut masks_ul = _mm_set_epi64x(up as i64, left as i64);
+    let mut masks_dr = _mm_set_epi64x(down as i64, right as i64);
+
+    let left_edge = _mm_set1_epi64x(Bitboard::LEFT_EDGE.raw() as i64);
+    let right_edge = _mm_set1_epi64x(Bitboard::RIGHT_EDGE.raw() as i64);
+
+    let road_occ = _mm_set1_epi64x(road_occ as i64);
+
+    let calc_next_masks = |masks| {
+        let next_masks_u = _mm_slli_epi64::<{ Square::STRIDE as i32 }>(masks);
+        let next_masks_d = _mm_srli_epi64::<{ Square::STRIDE as i32 }>(masks);
+        let next_masks_ud = _mm_or_si128(next_masks_u, next_masks_d);
+
+        let next_masks_l = _mm_andnot_si128(left_edge, _mm_slli_epi64::<1>(masks));
+        let next_masks_r = _mm_andnot_si128(right_edge, _mm_srli_epi64::<1>(masks));
+        let next_masks_lr = _mm_or_si128(next_masks_l, next_masks_r);
+
+        let next_masks = _mm_or_si128(next_masks_ud, next_masks_lr);
+
+        _mm_or_si128(masks, _mm_and_si128(next_masks, road_occ))
+    };
+
+    loop {
+        let next_masks_ul = calc_next_masks(masks_ul);
+        let next_masks_dr = calc_next_masks(masks_dr);
+
+        let unchanged_ul = _mm_cmpeq_epi64(next_masks_ul, masks_ul);
+        let unchanged_dr = _mm_cmpeq_epi64(next_masks_dr, masks_dr);
+        let unchanged = _mm_and_si128(unchanged_ul, unchanged_dr);
+
+        masks_ul = next_masks_ul;
+        masks_dr = next_masks_dr;
+
+        if _mm_movemask_epi8(unchanged) == 0xFFFF {
+            break;
+        }
+    }
+
+    let [left, up] = unsafe { std::mem::transmute::<__m128i, [u64; 2]>(masks_ul) };
+    let [right, down] = unsafe { std::mem::transmute::<__m128i, [u64; 2]>(masks_dr) };
+    [up, down, left, right]
+}
@@ -21,21 +21,29 @@
  * SOFTWARE.
  */
 
-use crate::board::Position;
-use crate::core::PieceType;
-use crate::eval::static_eval;
+use crate::bitboard::Bitboard;
+use crate::board::{FlatCountOutcome, Position};
+use crate::book::Book;
+use crate::core::{Direction, PieceType, Square};
+use crate::hits::find_hits;
+use crate::keys::Sfc64;
 use crate::limit::Limits;
-use crate::movegen::generate_moves;
+use crate::movegen::{generate_moves, generate_spread_moves};
 use crate::movepick::Movepicker;
+use crate::nnue::Network;
+use crate::road::has_road;
 use crate::takmove::Move;
 use crate::tei::TeiOptions;
-use crate::thread::{PvList, RootMove, SharedContext, TerminalState, ThreadData, update_pv};
-use crate::ttable::TtFlag;
+use crate::thread::{PvList, RootMove, SearchResult, SharedContext, TerminalState, ThreadData, update_pv};
+use crate::tinue::TinueSolver;
+use crate::ttable::{TtFileError, TtFlag};
+use crate::tunable;
 use crate::util::command_channel::{Receiver, Sender, channel};
-use std::sync::Arc;
+use std::path::Path;
+use std::sync::{Arc, mpsc};
 use std::thread;
 use std::thread::JoinHandle;
-use std::time::Instant;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
 pub const MAX_THREADS: u32 = 2048;
 
@@ -47,6 +55,8 @@ pub const SCORE_WIN: Score = 25000;
 
 pub const MAX_DEPTH: i32 = 255;
 
+const MAX_QSEARCH_DEPTH: i32 = 8;
+
 const WIDEN_REPORT_DELAY: f64 = 1.0;
 const VERBOSE_MULTIPV_DELAY: f64 = 1.0;
 
@@ -79,25 +89,41 @@ impl SearchContext {
 
 const LMR_TABLE_MOVES: usize = 64;
 
+#[must_use]
+fn calc_lmr_reduction(depth: usize, move_number: usize) -> i32 {
+    let base = tunable::lmr_base() as f64 / 1024.0;
+    let divisor = tunable::lmr_divisor() as f64 / 100.0;
+
+    let ln_depth = (depth as f64).ln();
+    let ln_move_number = (move_number as f64).ln();
+
+    ((base + ln_depth * ln_move_number / divisor) * 1024.0) as i32
+}
+
+#[cfg(not(feature = "tune"))]
 #[static_init::dynamic]
 static LMR_REDUCTIONS: [[i32; LMR_TABLE_MOVES]; MAX_DEPTH as usize] = {
-    const BASE: f64 = 0.5;
-    const DIVISOR: f64 = 2.5;
-
     let mut reductions = [[0; LMR_TABLE_MOVES]; MAX_DEPTH as usize];
 
-    for depth in 1..MAX_DEPTH as usize {
-        let ln_depth = (depth as f64).ln();
-        for move_number in 1..LMR_TABLE_MOVES {
-            let ln_move_number = (move_number as f64).ln();
-            let reduction = ((BASE + ln_depth * ln_move_number / DIVISOR) * 1024.0) as i32;
-            reductions[depth][move_number] = reduction;
+    for (depth, depth_reductions) in reductions.iter_mut().enumerate().skip(1) {
+        for (move_number, reduction) in depth_reductions.iter_mut().enumerate().skip(1) {
+            *reduction = calc_lmr_reduction(depth, move_number);
         }
     }
 
     reductions
 };
 
+// the table can't follow parameter changes, so tuning builds calculate reductions on the fly
+#[must_use]
+fn lmr_reduction(depth: i32, move_count: usize) -> i32 {
+    #[cfg(not(feature = "tune"))]
+    return LMR_REDUCTIONS[depth as usize - 1][move_count.min(LMR_TABLE_MOVES) - 1];
+
+    #[cfg(feature = "tune")]
+    return calc_lmr_reduction(depth as usize - 1, move_count.min(LMR_TABLE_MOVES) - 1);
+}
+
 #[derive(Clone)]
 struct PlyData {
     movelist: Vec<Move>,
@@ -169,20 +195,18 @@ fn search<NT: NodeType>(
         }
     }
 
-    thread.inc_nodes();
-
     if depth <= 0 {
-        let static_eval = static_eval(pos);
-        let correction = thread.corrhist.correction(pos);
-        return static_eval + correction;
+        return qsearch::<NT>(thread, data_stack, pos, 0, ply, alpha, beta);
     }
 
+    thread.inc_nodes();
+
     if NT::PV_NODE {
         thread.update_seldepth(ply);
     }
 
     if ply > MAX_DEPTH {
-        let static_eval = static_eval(pos);
+        let static_eval = thread.evaluate(pos);
         let correction = thread.corrhist.correction(pos);
         return static_eval + correction;
     }
@@ -209,20 +233,25 @@ fn search<NT: NodeType>(
         tt_entry.mv
     };
 
-    let raw_eval = static_eval(pos);
+    let raw_eval = thread.evaluate(pos);
     let correction = thread.corrhist.correction(pos);
     let static_eval = raw_eval + correction;
 
     if !NT::PV_NODE {
         // reverse futility pruning (rfp)
-        let rfp_margin = 100 * depth + 100 - (expected_cutnode as i32 * 50);
-        if depth <= 6 && static_eval - rfp_margin >= beta {
+        let rfp_margin = tunable::rfp_margin() * depth + tunable::rfp_margin_base()
+            - (expected_cutnode as i32 * tunable::rfp_cutnode_bonus());
+        if depth <= tunable::rfp_max_depth() && static_eval - rfp_margin >= beta {
             return static_eval;
         }
 
         // nullmove pruning (nmp)
-        if expected_cutnode && depth >= 4 && static_eval >= beta && thread.stack[ply as usize - 1].mv.is_some() {
-            let r = 3 + depth / 4;
+        if expected_cutnode
+            && depth >= tunable::nmp_min_depth()
+            && static_eval >= beta
+            && thread.stack[ply as usize - 1].mv.is_some()
+        {
+            let r = tunable::nmp_base_reduction() + depth / tunable::nmp_depth_divisor();
 
             let new_pos = thread.apply_nullmove(ply, pos);
 
@@ -277,10 +306,12 @@ fn search<NT: NodeType>(
             continue;
         }
 
-        if !NT::ROOT_NODE && best_score > -SCORE_WIN {
-            if depth <= 6 && move_count as i32 >= 5 + 2 * depth * depth {
-                break;
-            }
+        if !NT::ROOT_NODE
+            && best_score > -SCORE_WIN
+            && depth <= tunable::lmp_max_depth()
+            && move_count as i32 >= tunable::lmp_base() + tunable::lmp_depth_scale() * depth * depth
+        {
+            break;
         }
 
         let mut extension = 0;
@@ -314,7 +345,7 @@ fn search<NT: NodeType>(
             let new_depth = depth + extension - 1;
 
             if depth >= 2 && move_count >= 5 + 2 * usize::from(NT::ROOT_NODE) {
-                let mut r = LMR_REDUCTIONS[depth as usize - 1][move_count.min(LMR_TABLE_MOVES) - 1];
+                let mut r = lmr_reduction(depth, move_count);
 
                 if mv.is_spread() {
                     let gain = new_pos.fcd(pos.stm()) - pos.fcd(pos.stm());
@@ -457,7 +488,298 @@ fn search<NT: NodeType>(
     best_score
 }
 
-fn run_search(shared: Arc<SharedContext>, ctx: &SearchContext, thread: &mut ThreadData) {
+// cheap check for whether any spread could complete a road for the side to move. a spread
+// only changes squares in one rank or file, so each line is checked separately, assuming
+// every square in reach of their stacks (ignoring walls, which might be crushed) became theirs,
+// as well as any square a wall of theirs could uncover one of their flats on
+#[must_use]
+fn may_spread_into_road(pos: &Position) -> bool {
+    let stm = pos.stm();
+    let size = pos.size();
+    let limit = pos.carry_limit() as usize;
+    let open = pos.board() & !pos.piece_bb(PieceType::Capstone);
+
+    // stacks that can move at least `n + 1` squares
+    let mut movers = [Bitboard::empty(); Move::MAX_CARRY as usize];
+    for sq in pos.player_bb(stm) {
+        let height = (pos.stacks().height(sq) as usize).min(limit);
+        for bb in movers[..height].iter_mut() {
+            bb.set_sq(sq);
+        }
+    }
+
+    let roads = pos.roads(stm);
+
+    let along = |dirs: [Direction; 2]| {
+        let mut reach = Bitboard::empty();
+        for dir in dirs {
+            let mut frontier = Bitboard::empty();
+            for &bb in movers[..limit].iter().rev() {
+                frontier = (frontier | bb).shift(dir) & open;
+            }
+            reach |= frontier;
+        }
+        reach
+    };
+
+    // moving a wall off the top of a stack can leave one of their own flats behind, which
+    // is in the spread's line just like the squares it reaches
+    let mut uncovered = Bitboard::empty();
+    for sq in pos.player_bb(stm) & pos.piece_bb(PieceType::Wall) {
+        let height = pos.stacks().height(sq) as usize;
+        let players = pos.stacks().players(sq);
+
+        if (1..height.min(limit + 1)).any(|carry| (players >> (height - 1 - carry)) & 0x1 == stm.raw() as u128) {
+            uncovered.set_sq(sq);
+        }
+    }
+
+    let horizontal = along([Direction::Left, Direction::Right]) | uncovered;
+    let vertical = along([Direction::Up, Direction::Down]) | uncovered;
+
+    let ranks: [Bitboard; Square::STRIDE as usize] =
+        std::array::from_fn(|rank| Bitboard::LOWER_EDGE << (rank as u32 * Square::STRIDE));
+    let files: [Bitboard; Square::STRIDE as usize] = std::array::from_fn(|file| Bitboard::LEFT_EDGE << file as u32);
+
+    for (reach, lines) in [(horizontal, ranks), (vertical, files)] {
+        if !has_road(roads | reach, size) {
+            continue;
+        }
+
+        for line in lines {
+            let line_reach = reach & line;
+            if !line_reach.is_empty() && has_road(roads | line_reach, size) {
+                return true;
+            }
+        }
+    }
+
+    false
+}
+
+#[must_use]
+fn may_crush(pos: &Position) -> bool {
+    let caps = pos.player_bb(pos.stm()) & pos.piece_bb(PieceType::Capstone);
+
+    for sq in caps {
+        let max_dist = pos.stacks().height(sq).min(pos.carry_limit());
+        let hits = find_hits(pos.all_blockers(), sq, pos.size());
+
+        for (dist, hit_sq) in hits {
+            if dist > 0 && dist <= max_dist && pos.stacks().top(hit_sq) == Some(PieceType::Wall) {
+                return true;
+            }
+        }
+    }
+
+    false
+}
+
+// only searches moves that win on the spot, crush walls, or block the opponent's road threats
+fn qsearch<NT: NodeType>(
+    thread: &mut ThreadData,
+    data_stack: &mut [PlyData],
+    pos: &Position,
+    depth: i32,
+    ply: i32,
+    mut alpha: Score,
+    beta: Score,
+) -> Score {
+    if thread.shared().has_stopped() {
+        return 0;
+    }
+
+    if thread.is_main_thread() && thread.root_depth > 1 && thread.shared().check_stop_hard(thread.nodes()) {
+        return 0;
+    }
+
+    let stm = pos.stm();
+
+    // placing a road piece wins immediately
+    if !pos.placement_road_threats(stm).is_empty() {
+        return SCORE_MATE - ply - 1;
+    }
+
+    alpha = alpha.max(-SCORE_MATE + ply);
+    let beta = beta.min(SCORE_MATE - ply);
+    if alpha >= beta {
+        return alpha;
+    }
+
+    thread.inc_nodes();
+
+    if NT::PV_NODE {
+        thread.update_seldepth(ply);
+    }
+
+    let static_eval = thread.evaluate(pos) + thread.corrhist.correction(pos);
+
+    // chains of blocks and counter-threats can otherwise go on for a very long time
+    if ply >= MAX_DEPTH || depth <= -MAX_QSEARCH_DEPTH {
+        return static_eval;
+    }
+
+    let (_tt_hit, tt_entry) = thread.shared().tt.probe(pos.key(), ply);
+
+    if !NT::PV_NODE
+        && match tt_entry.flag {
+            None => false,
+            Some(TtFlag::UpperBound) => tt_entry.score <= alpha,
+            Some(TtFlag::LowerBound) => tt_entry.score >= beta,
+            Some(TtFlag::Exact) => true,
+        }
+    {
+        return tt_entry.score;
+    }
+
+    // if the opponent can place a road piece to win, only moves that stop them are worth
+    // anything, so standing pat is not an option and having no such move loses
+    let threatened = !pos.placement_road_threats(stm.flip()).is_empty();
+
+    let mut best_score = if threatened { -SCORE_MATE + ply + 2 } else { static_eval };
+
+    if best_score >= beta {
+        return best_score;
+    }
+
+    alpha = alpha.max(best_score);
+
+    let spread_road_possible = may_spread_into_road(pos);
+    let crush_possible = may_crush(pos);
+
+    if !threatened && !spread_road_possible && !crush_possible {
+        return best_score;
+    }
+
+    let (data, child_data) = data_stack.split_first_mut().unwrap();
+
+    if threatened {
+        generate_moves(&mut data.movelist, pos);
+    } else {
+        generate_spread_moves(&mut data.movelist, pos);
+    }
+
+    if let Some(tt_move) = tt_entry.mv
+        && let Some(idx) = data.movelist.iter().position(|&mv| mv == tt_move)
+    {
+        data.movelist.swap(0, idx);
+    }
+
+    let mut best_move = None;
+    let mut tt_flag = TtFlag::UpperBound;
+
+    for idx in 0..data.movelist.len() {
+        let mv = data.movelist[idx];
+
+        debug_assert!(pos.is_legal(mv));
+
+        let is_crush = mv.is_spread() && pos.stacks().top(mv.spread_dest()) == Some(PieceType::Wall);
+        let may_win = mv.is_spread() && spread_road_possible;
+
+        // placements that can win were already handled above
+        if !threatened && !is_crush && !may_win {
+            continue;
+        }
+
+        let new_pos = pos.apply_move(mv);
+
+        if may_win && new_pos.has_road(stm) {
+            return SCORE_MATE - ply - 1;
+        }
+
+        let ends_game = !matches!(new_pos.count_flats(), FlatCountOutcome::None);
+
+        let relevant = if threatened {
+            ends_game || new_pos.placement_road_threats(stm.flip()).is_empty()
+        } else {
+            is_crush
+        };
+
+        if !relevant {
+            continue;
+        }
+
+        if NT::PV_NODE {
+            child_data[0].pv.clear();
+        }
+
+        thread.push_move(ply, pos, &new_pos, mv);
+
+        let score = if let Some(state) = thread.check_terminal_state(ply, &new_pos, mv) {
+            match state {
+                TerminalState::Win => SCORE_MATE - ply - 1,
+                TerminalState::Draw => 0,
+                TerminalState::Loss => -SCORE_MATE + ply + 1,
+            }
+        } else {
+            -qsearch::<NT>(thread, child_data, &new_pos, depth - 1, ply + 1, -beta, -alpha)
+        };
+
+        thread.pop_move();
+
+        if thread.shared().has_stopped() {
+            return 0;
+        }
+
+        if score > best_score {
+            best_score = score;
+        }
+
+        if score > alpha {
+            alpha = score;
+            best_move = Some(mv);
+
+            if NT::PV_NODE {
+                update_pv(&mut data.pv, mv, &child_data[0].pv);
+            }
+
+            tt_flag = TtFlag::Exact;
+        }
+
+        if score >= beta {
+            tt_flag = TtFlag::LowerBound;
+            break;
+        }
+    }
+
+    thread
+        .shared()
+        .tt
+        .store(pos.key(), best_score, best_move, 0, ply, tt_flag);
+
+    best_score
+}
+
+// without a tinue, any legal move is played so that the search still ends with a bestmove
+fn run_tinue(shared: Arc<SharedContext>, pos: &Position, max_depth: i32) {
+    let counter = shared.get_counter();
+
+    let line = TinueSolver::new(&shared).solve(pos, max_depth);
+
+    let mv = match line {
+        Some(line) => Some(line[0]),
+        None => {
+            if !shared.has_stopped() {
+                println!("info string no tinue found within depth {}", max_depth);
+            }
+
+            let mut moves = Vec::new();
+            generate_moves(&mut moves, pos);
+            moves.first().copied()
+        }
+    };
+
+    drop(shared);
+
+    // the search must be marked complete before bestmove, which may immediately be followed by another go
+    counter.complete_search();
+
+    if let Some(mv) = mv {
+        println!("bestmove {}", mv);
+    }
+}
+
+fn run_search(shared: Arc<SharedContext>, ctx: Box<SearchContext>, thread: &mut ThreadData) {
     assert!(thread.shared.is_none());
     thread.shared = Some(shared);
 
@@ -474,6 +796,9 @@ fn run_search(shared: Arc<SharedContext>, ctx: &SearchContext, thread: &mut Thre
     thread.key_history.reserve(ctx.key_history.len());
     thread.key_history.extend_from_slice(&ctx.key_history);
 
+    let network = thread.shared().network.clone();
+    thread.nnue.reset(network, &ctx.root_pos);
+
     counter.register_thread();
 
     let mut data_stack = vec![PlyData::new(); MAX_DEPTH as usize * 2];
@@ -489,7 +814,7 @@ fn run_search(shared: Arc<SharedContext>, ctx: &SearchContext, thread: &mut Thre
         while thread.pv_idx < ctx.multipv {
             thread.reset_seldepth();
 
-            let mut delta = 25;
+            let mut delta = tunable::aspiration_delta();
 
             let mut alpha = -SCORE_INF;
             let mut beta = SCORE_INF;
@@ -518,7 +843,11 @@ fn run_search(shared: Arc<SharedContext>, ctx: &SearchContext, thread: &mut Thre
                     break;
                 }
 
-                if thread.is_main_thread() && !thread.shared().options.minimal && ctx.multipv == 1 {
+                if thread.is_main_thread()
+                    && !thread.shared().quiet
+                    && !thread.shared().options.minimal
+                    && ctx.multipv == 1
+                {
                     let time = thread.shared().elapsed();
                     if time >= WIDEN_REPORT_DELAY {
                         let nodes = thread.shared().total_nodes();
@@ -549,9 +878,10 @@ fn run_search(shared: Arc<SharedContext>, ctx: &SearchContext, thread: &mut Thre
                     thread.shared().stop();
                 }
 
-                if thread.shared().has_stopped()
-                    || (!thread.shared().options.minimal
-                        && (last_pv || thread.shared().elapsed() >= VERBOSE_MULTIPV_DELAY))
+                if !thread.shared().quiet
+                    && (thread.shared().has_stopped()
+                        || (!thread.shared().options.minimal
+                            && (last_pv || thread.shared().elapsed() >= VERBOSE_MULTIPV_DELAY)))
                 {
                     report(thread, thread.root_depth, thread.shared().elapsed(), ctx.multipv);
                 }
@@ -573,14 +903,33 @@ fn run_search(shared: Arc<SharedContext>, ctx: &SearchContext, thread: &mut Thre
 
     if thread.is_main_thread() {
         counter.unregister_and_wait();
+        thread.shared().wait_for_ponder_end();
 
         let time = thread.shared().elapsed();
-        final_report(thread, thread.root_depth, time, ctx.multipv);
+        let ponder = thread.shared().options.ponder;
+        let multipv = ctx.multipv;
+
+        let quiet = thread.shared().quiet;
+        if quiet {
+            thread.shared().set_result(search_result(thread));
+        }
 
+        // release the shared root moves and key history before the searcher may reuse them
+        thread.nnue.release();
         thread.shared = None;
+        drop(ctx);
+
+        // the search must be marked complete before bestmove, which may immediately be followed by another go
         counter.complete_search();
+
+        if !quiet {
+            final_report(thread, thread.root_depth, time, multipv, ponder);
+        }
     } else {
+        thread.nnue.release();
         thread.shared = None;
+        drop(ctx);
+
         counter.unregister_thread();
     }
 }
@@ -678,15 +1027,42 @@ fn report(thread: &ThreadData, depth: i32, time: f64, multipv: usize) {
     }
 }
 
-fn final_report(thread: &ThreadData, _depth: i32, _time: f64, _multipv: usize) {
-    let mv = thread.pv_move().mv();
-    println!("bestmove {}", mv);
+#[must_use]
+fn search_result(thread: &ThreadData) -> SearchResult {
+    let root_move = thread.pv_move();
+
+    // the last iteration may not have finished
+    let (depth, score) = if root_move.score == -SCORE_INF {
+        ((thread.root_depth - 1).max(1), root_move.previous_score)
+    } else {
+        (thread.root_depth, root_move.display_score)
+    };
+
+    SearchResult {
+        mv: root_move.mv(),
+        score,
+        depth,
+        nodes: thread.shared().total_nodes(),
+    }
+}
+
+fn final_report(thread: &ThreadData, _depth: i32, _time: f64, _multipv: usize, ponder: bool) {
+    let root_move = thread.pv_move();
+    print!("bestmove {}", root_move.mv());
+
+    if ponder && let Some(ponder_move) = root_move.pv.get(1) {
+        print!(" ponder {}", ponder_move);
+    }
+
+    println!();
 }
 
 #[derive(Clone)]
 enum ThreadCommand {
     Ping,
-    StartSearch(Arc<SharedContext>, SearchContext),
+    StartSearch(Arc<SharedContext>, Box<SearchContext>),
+    Tinue(Arc<SharedContext>, Box<Position>, i32),
+    Correction(Box<Position>, mpsc::Sender<Score>),
     Clear,
     Quit,
 }
@@ -697,6 +1073,8 @@ pub struct Searcher {
     sender: Sender<ThreadCommand>,
     root_moves: Arc<Vec<RootMove>>,
     key_history: Arc<Vec<u64>>,
+    book: Option<Arc<Book>>,
+    book_rng: Sfc64,
 }
 
 impl Searcher {
@@ -726,6 +1104,13 @@ impl Searcher {
             sender,
             root_moves: Arc::new(Vec::with_capacity(1024)),
             key_history: Arc::new(Vec::with_capacity(1024)),
+            book: None,
+            book_rng: Sfc64::new(
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|time| time.as_nanos() as u64)
+                    .unwrap_or(0),
+            ),
         }
     }
 
@@ -735,7 +1120,17 @@ impl Searcher {
         loop {
             match receiver.recv(|cmd| cmd.clone()) {
                 ThreadCommand::Ping => {}
-                ThreadCommand::StartSearch(shared, ctx) => run_search(shared, &ctx, &mut data),
+                ThreadCommand::StartSearch(shared, ctx) => run_search(shared, ctx, &mut data),
+                ThreadCommand::Tinue(shared, pos, max_depth) => {
+                    if data.is_main_thread() {
+                        run_tinue(shared, &pos, max_depth);
+                    }
+                }
+                ThreadCommand::Correction(pos, reply) => {
+                    if data.is_main_thread() {
+                        let _ = reply.send(data.corrhist.correction(&pos));
+                    }
+                }
                 ThreadCommand::Clear => {
                     data.corrhist.clear();
                     data.history.clear();
@@ -756,9 +1151,31 @@ impl Searcher {
         max_depth: i32,
         moves_to_search: &[Move],
         options: &TeiOptions,
+        ponder: bool,
     ) {
+        // a book move can't be sent until the ponder search is over, so ponder searches always search
+        if options.own_book
+            && !ponder
+            && let Some(mv) = self.probe_book(pos, moves_to_search)
+        {
+            if self.shared_ctx.quiet {
+                self.modify_shared_ctx(|ctx| {
+                    ctx.set_result(SearchResult {
+                        mv,
+                        score: 0,
+                        depth: 0,
+                        nodes: 0,
+                    })
+                });
+            } else {
+                println!("info string book move");
+                println!("bestmove {}", mv);
+            }
+            return;
+        }
+
         self.modify_shared_ctx(|ctx| {
-            ctx.init_search(options, start_time, limits);
+            ctx.init_search(options, start_time, limits, ponder);
         });
 
         self.init_root_moves(pos, moves_to_search);
@@ -783,11 +1200,35 @@ impl Searcher {
         );
 
         self.sender
-            .send(ThreadCommand::StartSearch(self.shared_ctx.clone(), ctx));
+            .send(ThreadCommand::StartSearch(self.shared_ctx.clone(), Box::new(ctx)));
+    }
+
+    // proves or disproves tinue on the main thread, ending with a bestmove like any other search
+    pub fn start_tinue(&mut self, pos: &Position, start_time: Instant, max_depth: i32, options: &TeiOptions) {
+        self.modify_shared_ctx(|ctx| {
+            ctx.init_search(options, start_time, Limits::new(start_time), false);
+        });
+
+        self.sender
+            .send(ThreadCommand::Tinue(self.shared_ctx.clone(), Box::new(*pos), max_depth));
+    }
+
+    #[must_use]
+    fn probe_book(&mut self, pos: &Position, moves_to_search: &[Move]) -> Option<Move> {
+        let book = self.book.as_deref()?;
+        let mv = book.probe(pos, &mut self.book_rng)?;
+
+        // a restricted search may not be able to play the book's choice
+        (moves_to_search.is_empty() || moves_to_search.contains(&mv)).then_some(mv)
     }
 
     pub fn stop(&mut self) {
         self.shared_ctx.stop();
+        self.shared_ctx.end_ponder();
+    }
+
+    pub fn ponderhit(&mut self) {
+        self.shared_ctx.end_ponder();
     }
 
     pub fn is_searching(&self) -> bool {
@@ -806,12 +1247,12 @@ impl Searcher {
         }
     }
 
-    fn modify_shared_ctx<F>(&mut self, func: F)
+    fn modify_shared_ctx<F, R>(&mut self, func: F) -> R
     where
-        F: Fn(&mut SharedContext),
+        F: FnOnce(&mut SharedContext) -> R,
     {
         let ctx = Arc::get_mut(&mut self.shared_ctx).unwrap();
-        func(ctx);
+        func(ctx)
     }
 
     pub fn reset(&mut self) {
@@ -824,6 +1265,42 @@ impl Searcher {
         self.sender.send(ThreadCommand::Clear);
     }
 
+    pub fn set_quiet(&mut self, quiet: bool) {
+        self.modify_shared_ctx(|ctx| ctx.quiet = quiet);
+    }
+
+    // the outcome of the last search, only kept by quiet searchers
+    #[must_use]
+    pub fn last_result(&self) -> Option<SearchResult> {
+        self.shared_ctx.result()
+    }
+
+    // what the main thread's correction history would currently add to the static eval
+    #[must_use]
+    pub fn correction(&mut self, pos: &Position) -> Score {
+        // receivers only clone the command before `send` returns, so wait for the reply
+        let (reply, result) = mpsc::channel();
+        self.sender.send(ThreadCommand::Correction(Box::new(*pos), reply));
+        result.recv().unwrap_or(0)
+    }
+
+    pub fn set_book(&mut self, book: Option<Arc<Book>>) {
+        self.book = book;
+    }
+
+    pub fn set_network(&mut self, network: Option<Arc<Network>>) {
+        self.modify_shared_ctx(|ctx| ctx.network = network);
+    }
+
+    pub fn save_tt(&self, path: &Path) -> Result<(), TtFileError> {
+        self.shared_ctx.tt.save(path)
+    }
+
+    pub fn load_tt(&mut self, path: &Path) -> Result<(), TtFileError> {
+        let thread_count = self.threads.len();
+        self.modify_shared_ctx(|ctx| ctx.tt.load(path, thread_count))
+    }
+
     pub fn set_tt_size(&mut self, size_mib: usize) {
         let thread_count = self.threads.len();
         self.modify_shared_ctx(|ctx| {
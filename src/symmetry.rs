@@ -0,0 +1,183 @@
+/*
+ * syntaks, a TEI Tak engine
+ * Copyright (c) 2026 Ciekce
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use crate::board::Position;
+use crate::core::{Direction, Player, Square};
+use crate::keys;
+use crate::takmove::Move;
+
+// the 8 symmetries of a square board. rotations are clockwise
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Symmetry {
+    Identity,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    MirrorFiles,
+    MirrorRanks,
+    Transpose,
+    AntiTranspose,
+}
+
+impl Symmetry {
+    pub const COUNT: usize = 8;
+
+    pub const ALL: [Self; Self::COUNT] = [
+        Self::Identity,
+        Self::Rotate90,
+        Self::Rotate180,
+        Self::Rotate270,
+        Self::MirrorFiles,
+        Self::MirrorRanks,
+        Self::Transpose,
+        Self::AntiTranspose,
+    ];
+
+    // (swap file and rank, then mirror files, then mirror ranks)
+    #[must_use]
+    const fn parts(self) -> (bool, bool, bool) {
+        match self {
+            Self::Identity => (false, false, false),
+            Self::Rotate90 => (true, false, true),
+            Self::Rotate180 => (false, true, true),
+            Self::Rotate270 => (true, true, false),
+            Self::MirrorFiles => (false, true, false),
+            Self::MirrorRanks => (false, false, true),
+            Self::Transpose => (true, false, false),
+            Self::AntiTranspose => (true, true, true),
+        }
+    }
+
+    #[must_use]
+    pub const fn inverse(self) -> Self {
+        match self {
+            Self::Rotate90 => Self::Rotate270,
+            Self::Rotate270 => Self::Rotate90,
+            other => other,
+        }
+    }
+
+    #[must_use]
+    pub fn apply_square(self, sq: Square, size: u32) -> Square {
+        debug_assert!(sq.file() < size && sq.rank() < size);
+
+        let (swap, mirror_files, mirror_ranks) = self.parts();
+
+        let (mut file, mut rank) = (sq.file(), sq.rank());
+
+        if swap {
+            (file, rank) = (rank, file);
+        }
+        if mirror_files {
+            file = size - 1 - file;
+        }
+        if mirror_ranks {
+            rank = size - 1 - rank;
+        }
+
+        Square::from_file_rank(file, rank).unwrap()
+    }
+
+    #[must_use]
+    pub fn apply_direction(self, dir: Direction) -> Direction {
+        let (swap, mirror_files, mirror_ranks) = self.parts();
+
+        let mut dir = dir;
+
+        if swap {
+            dir = match dir {
+                Direction::Up => Direction::Right,
+                Direction::Down => Direction::Left,
+                Direction::Left => Direction::Down,
+                Direction::Right => Direction::Up,
+            };
+        }
+        if mirror_files {
+            dir = match dir {
+                Direction::Left => Direction::Right,
+                Direction::Right => Direction::Left,
+                other => other,
+            };
+        }
+        if mirror_ranks {
+            dir = match dir {
+                Direction::Up => Direction::Down,
+                Direction::Down => Direction::Up,
+                other => other,
+            };
+        }
+
+        dir
+    }
+
+    // spreads keep their drop pattern, only the origin and direction change
+    #[must_use]
+    pub fn apply_move(self, mv: Move, size: u32) -> Move {
+        let sq = self.apply_square(mv.sq(), size);
+
+        if mv.is_spread() {
+            Move::spread(sq, self.apply_direction(mv.dir()), mv.pattern())
+        } else {
+            Move::placement(mv.pt(), sq)
+        }
+    }
+
+    // the key `pos.transform(self)` would have, without building it
+    #[must_use]
+    pub fn apply_key(self, pos: &Position) -> u64 {
+        let mut key = match pos.stm() {
+            Player::P1 => 0,
+            Player::P2 => keys::p2_key(),
+        };
+
+        for sq in pos.occ() {
+            let dst = self.apply_square(sq, pos.size());
+
+            key ^= keys::top_key(pos.stacks().top(sq).unwrap(), dst);
+
+            for (height, player) in pos.stacks().iter(sq).enumerate() {
+                key ^= keys::player_key(height as u8, player, dst);
+            }
+        }
+
+        debug_assert_eq!(key, pos.transform(self).key());
+
+        key
+    }
+}
+
+// the smallest key over all symmetries of `pos`, and a symmetry that produces it.
+// positions that are mirror images of each other share the same canonical key
+#[must_use]
+pub fn canonical(pos: &Position) -> (u64, Symmetry) {
+    Symmetry::ALL
+        .into_iter()
+        .map(|sym| (sym.apply_key(pos), sym))
+        .min_by_key(|&(key, _)| key)
+        .unwrap()
+}
+
+#[must_use]
+pub fn canonical_key(pos: &Position) -> u64 {
+    canonical(pos).0
+}
@@ -21,7 +21,6 @@
  * SOFTWARE.
  */
 
-use crate::board::Position;
 use crate::core::*;
 use std::fmt::{Display, Formatter};
 use std::num::NonZeroU16;
@@ -34,7 +33,7 @@ pub struct Move {
 
 impl Move {
     const SQUARE_BITS: usize = 6;
-    const PATTERN_BITS: usize = 6;
+    const PATTERN_BITS: usize = 8;
     const FLAG_BITS: usize = 2;
     pub const TOTAL_BITS: usize = Self::SQUARE_BITS + Self::PATTERN_BITS + Self::FLAG_BITS;
 
@@ -46,6 +45,9 @@ impl Move {
     pub const PATTERN_MASK: u16 = (1 << Self::PATTERN_BITS) - 1;
     pub const FLAG_MASK: u16 = (1 << Self::FLAG_BITS) - 1;
 
+    // carry limit of the largest board size, patterns are always encoded relative to this
+    pub const MAX_CARRY: u8 = Self::PATTERN_BITS as u8;
+
     #[must_use]
     pub const fn placement(pt: PieceType, dst: Square) -> Self {
         let mut raw = 0;
@@ -124,6 +126,12 @@ impl Move {
         self.pattern().count_ones() as u8
     }
 
+    #[must_use]
+    pub const fn taken(self) -> u8 {
+        assert!(self.is_spread());
+        Self::MAX_CARRY - self.pattern().trailing_zeros() as u8
+    }
+
     #[must_use]
     pub const fn spread_dest(self) -> Square {
         assert!(self.is_spread());
@@ -142,14 +150,14 @@ impl Display for Move {
             let pattern = self.pattern();
 
             let dropped = pattern.trailing_zeros();
-            let taken = 6 - dropped;
+            let taken = self.taken();
 
             if taken == 1 {
                 write!(f, "{}{}", self.sq(), self.dir())?;
             } else {
                 write!(f, "{}{}{}", taken, self.sq(), self.dir())?;
                 if pattern.count_ones() > 1 {
-                    let mut pattern = ((pattern | (1 << Position::CARRY_LIMIT)) >> dropped) & !1;
+                    let mut pattern = ((pattern | (1 << Self::MAX_CARRY)) >> dropped) & !1;
                     while pattern != 0 {
                         let dropped = pattern.trailing_zeros();
                         pattern = (pattern >> dropped) & !1;
@@ -248,7 +256,11 @@ impl FromStr for Move {
         let taken = taken.unwrap_or(1);
         let bytes = bytes.strip_suffix(b"*").unwrap_or(bytes);
 
-        if (s.len() - next) > 6 {
+        if taken > Self::MAX_CARRY {
+            return Err(MoveStrError::TooManySpreadPieces);
+        }
+
+        if (bytes.len() - next) > Self::MAX_CARRY as usize {
             return Err(MoveStrError::TooManySpreadSteps);
         }
 
@@ -256,7 +268,7 @@ impl FromStr for Move {
         let mut bit = 1;
 
         for &pattern_char in &bytes[next..] {
-            if !(b'1'..=b'6').contains(&pattern_char) {
+            if !(b'1'..=b'8').contains(&pattern_char) {
                 return Err(MoveStrError::InvalidSpreadPattern);
             }
 
@@ -266,9 +278,9 @@ impl FromStr for Move {
             pattern |= bit;
         }
 
-        pattern <<= Position::CARRY_LIMIT - taken;
+        pattern <<= Self::MAX_CARRY - taken;
 
-        if (pattern & !((1 << (Position::CARRY_LIMIT + 1)) - 1)) != 0 {
+        if (pattern & !((1 << (Self::MAX_CARRY + 1)) - 1)) != 0 {
             return Err(MoveStrError::TooManySpreadPieces);
         }
 
@@ -21,26 +21,38 @@
  * SOFTWARE.
  */
 
+use crate::bench;
 use crate::board::Position;
-use crate::core::Player;
-use crate::eval::static_eval;
+use crate::book::Book;
+use crate::core::{MAX_SIZE, MIN_SIZE, Player};
+use crate::eval::{format_flats, print_trace, static_eval};
 use crate::limit::Limits;
-use crate::perft::{perft, split_perft};
+use crate::nnue::Network;
+use crate::perft;
+use crate::perft::{compare_perft, print_perft, split_perft};
+use crate::ptn::PtnGame;
 use crate::search;
 use crate::search::{MAX_THREADS, Searcher};
-use crate::ttable::{DEFAULT_TT_SIZE_MIB, MAX_TT_SIZE_MIB};
+use crate::tinue::{DEFAULT_TINUE_DEPTH, MAX_TINUE_DEPTH};
+use crate::ttable::{DEFAULT_TT_SIZE_MIB, MAX_TT_SIZE_MIB, TtFileError};
+#[cfg(feature = "tune")]
+use crate::tunable;
+use std::path::Path;
+use std::sync::Arc;
 use std::time::Instant;
 
 const NAME: &str = "syntaks";
 const AUTHORS: &str = "Ciekce";
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
-pub const MAX_MULTIPV: usize = 2048;
+pub(crate) const MAX_MULTIPV: usize = 2048;
 
 #[derive(Copy, Clone, Debug)]
-pub struct TeiOptions {
+pub(crate) struct TeiOptions {
     pub multipv: usize,
     pub minimal: bool,
+    pub ponder: bool,
+    pub own_book: bool,
 }
 
 impl Default for TeiOptions {
@@ -48,25 +60,33 @@ impl Default for TeiOptions {
         Self {
             multipv: 1,
             minimal: false,
+            ponder: false,
+            own_book: false,
         }
     }
 }
 
 struct TeiHandler {
+    size: u32,
+    half_komi: u32,
     pos: Position,
     key_history: Vec<u64>,
     searcher: Searcher,
     options: TeiOptions,
+    network: Option<Arc<Network>>,
 }
 
 impl TeiHandler {
     #[must_use]
     fn new() -> Self {
         Self {
-            pos: Position::startpos(),
+            size: Position::DEFAULT_SIZE,
+            half_komi: Position::DEFAULT_HALF_KOMI,
+            pos: Position::startpos(Position::DEFAULT_SIZE),
             key_history: Vec::with_capacity(1024),
             searcher: Searcher::new(),
             options: TeiOptions::default(),
+            network: None,
         }
     }
 
@@ -95,10 +115,18 @@ impl TeiHandler {
                 "position" => self.handle_position(args),
                 "go" => self.handle_go(args, start_time),
                 "stop" => self.handle_stop(),
+                "ponderhit" => self.handle_ponderhit(),
                 "wait" => self.handle_wait(),
-                "d" => self.handle_d(),
+                "d" => self.handle_d(args),
+                "eval" => self.handle_eval(),
+                "bench" => self.handle_bench(args),
                 "perft" => self.handle_perft(args),
                 "splitperft" => self.handle_splitperft(args),
+                "perftcompare" => self.handle_perftcompare(args),
+                "savehash" => self.handle_savehash(args),
+                "loadhash" => self.handle_loadhash(args),
+                #[cfg(feature = "tune")]
+                "spsa" => tunable::print_spsa(),
                 "quit" => break,
                 unknown => eprintln!("Unknown command '{}'", unknown),
             }
@@ -108,14 +136,13 @@ impl TeiHandler {
     }
 
     fn handle_tei(&self) {
-        let half_komi = Position::KOMI * 2;
-
         println!("id name {} {}", NAME, VERSION);
         println!("id author {}", AUTHORS);
 
         println!(
-            "option name HalfKomi type spin default {} min {} max {}",
-            half_komi, half_komi, half_komi
+            "option name HalfKomi type spin default {} min 0 max {}",
+            Position::DEFAULT_HALF_KOMI,
+            Position::MAX_HALF_KOMI
         );
 
         println!(
@@ -129,6 +156,17 @@ impl TeiHandler {
 
         println!("option name Minimal type check default false");
 
+        println!("option name Ponder type check default false");
+
+        println!("option name EvalFile type string default <empty>");
+
+        println!("option name OwnBook type check default false");
+
+        println!("option name BookFile type string default <empty>");
+
+        #[cfg(feature = "tune")]
+        tunable::print_options();
+
         println!("teiok");
     }
 
@@ -139,19 +177,26 @@ impl TeiHandler {
         }
 
         if args.is_empty() {
-            println!("info string Missing size, assuming 6x6");
+            println!("info string Missing size, assuming {0}x{0}", Position::DEFAULT_SIZE);
+            self.size = Position::DEFAULT_SIZE;
         } else {
             match args[0].parse::<u32>() {
+                Ok(size) if (MIN_SIZE..=MAX_SIZE).contains(&size) => self.size = size,
                 Ok(size) => {
-                    if size != 6 {
-                        eprintln!("Only 6x6 supported");
-                        return;
-                    }
+                    eprintln!("Unsupported size {}", size);
+                    return;
+                }
+                Err(_) => {
+                    eprintln!("Invalid size");
+                    return;
                 }
-                Err(_) => eprintln!("Invalid size"),
             }
         }
 
+        self.pos = Position::startpos(self.size);
+        self.pos.set_half_komi(self.half_komi);
+        self.key_history.clear();
+
         self.searcher.reset();
     }
 
@@ -198,10 +243,17 @@ impl TeiHandler {
 
         match name.as_str() {
             "halfkomi" => {
-                if let Ok(half_komi) = value.parse::<u32>()
-                    && half_komi != Position::KOMI * 2
-                {
-                    eprintln!("Unsupported komi value");
+                match value.parse::<u32>() {
+                    Ok(half_komi) if half_komi <= Position::MAX_HALF_KOMI => {
+                        // cached scores are only valid for the komi they were searched with
+                        if half_komi != self.half_komi {
+                            self.searcher.reset();
+                        }
+
+                        self.half_komi = half_komi;
+                        self.pos.set_half_komi(half_komi);
+                    }
+                    _ => eprintln!("Invalid half komi '{}'", value),
                 }
             }
             "hash" => {
@@ -227,7 +279,26 @@ impl TeiHandler {
                     self.options.minimal = minimal;
                 }
             }
-            unknown => eprintln!("Unknown option '{}'", unknown),
+            "ponder" => {
+                if let Ok(ponder) = value.parse::<bool>() {
+                    self.options.ponder = ponder;
+                }
+            }
+            "evalfile" => self.set_eval_file(&value),
+            "ownbook" => {
+                if let Ok(own_book) = value.parse::<bool>() {
+                    self.options.own_book = own_book;
+                }
+            }
+            "bookfile" => self.set_book_file(&value),
+            unknown => {
+                #[cfg(feature = "tune")]
+                if tunable::set_option(unknown, &value) {
+                    return;
+                }
+
+                eprintln!("Unknown option '{}'", unknown);
+            }
         }
     }
 
@@ -251,7 +322,8 @@ impl TeiHandler {
 
         match pos_type {
             "startpos" => {
-                self.pos = Position::startpos();
+                self.pos = Position::startpos(self.size);
+                self.pos.set_half_komi(self.half_komi);
                 self.key_history.clear();
             }
             "tps" => {
@@ -265,6 +337,7 @@ impl TeiHandler {
                 match Position::from_tps_parts(&args[0..count]) {
                     Ok(pos) => {
                         self.pos = pos;
+                        self.pos.set_half_komi(self.half_komi);
                         self.key_history.clear();
                     }
                     Err(err) => {
@@ -275,6 +348,46 @@ impl TeiHandler {
 
                 next += count;
             }
+            "ptn" => {
+                let count = args.iter().position(|&s| s == "moves").unwrap_or(args.len());
+
+                if count == 0 {
+                    eprintln!("Missing PTN path");
+                    return;
+                }
+
+                let path = args[0..count].join(" ");
+
+                let ptn = match std::fs::read_to_string(&path) {
+                    Ok(ptn) => ptn,
+                    Err(err) => {
+                        eprintln!("Failed to read PTN file '{}': {}", path, err);
+                        return;
+                    }
+                };
+
+                match ptn.parse::<PtnGame>() {
+                    Ok(game) => {
+                        if game.start_pos().half_komi() != self.pos.half_komi() {
+                            self.searcher.reset();
+                        }
+
+                        self.pos = *game.start_pos();
+                        self.key_history.clear();
+
+                        for &mv in game.moves() {
+                            self.key_history.push(self.pos.key());
+                            self.pos = self.pos.apply_move(mv);
+                        }
+                    }
+                    Err(err) => {
+                        eprintln!("Failed to parse PTN: {:?}", err);
+                        return;
+                    }
+                }
+
+                next += count;
+            }
             _ => {
                 eprintln!("Invalid position type {}", pos_type);
                 return;
@@ -309,8 +422,24 @@ impl TeiHandler {
             return;
         }
 
+        if args.first() == Some(&"tinue") {
+            self.handle_go_tinue(&args[1..], start_time);
+            return;
+        }
+
+        if let Some(network) = self.network.as_deref()
+            && !network.supports(&self.pos)
+        {
+            println!(
+                "info string Warning: network is for size {} and half komi {}, using handcrafted eval",
+                network.size(),
+                network.half_komi()
+            );
+        }
+
         let mut limits = Limits::new(start_time);
         let mut max_depth = None;
+        let mut ponder = false;
 
         let mut wtime = None;
         let mut btime = None;
@@ -324,6 +453,7 @@ impl TeiHandler {
             let limit_str = args[i];
             match limit_str {
                 "infinite" => {}
+                "ponder" => ponder = true,
                 "depth" => {
                     i += 1;
                     if i >= args.len() {
@@ -455,33 +585,215 @@ impl TeiHandler {
             max_depth,
             &moves_to_search,
             &self.options,
+            ponder,
         );
     }
 
+    fn handle_go_tinue(&mut self, args: &[&str], start_time: Instant) {
+        let mut depth = DEFAULT_TINUE_DEPTH;
+
+        match args {
+            [] => {}
+            ["depth", depth_str] => match depth_str.parse::<i32>() {
+                Ok(max_depth) if max_depth > 0 => depth = max_depth.min(MAX_TINUE_DEPTH),
+                _ => {
+                    eprintln!("Invalid depth '{}'", depth_str);
+                    return;
+                }
+            },
+            ["depth"] => {
+                eprintln!("Missing depth");
+                return;
+            }
+            [arg, ..] => {
+                eprintln!("Unexpected argument '{}'", arg);
+                return;
+            }
+        }
+
+        self.searcher.start_tinue(&self.pos, start_time, depth, &self.options);
+    }
+
     fn handle_stop(&mut self) {
         self.searcher.stop();
     }
 
+    fn handle_ponderhit(&mut self) {
+        self.searcher.ponderhit();
+    }
+
     fn handle_wait(&mut self) {
         self.searcher.wait();
     }
 
-    fn handle_d(&self) {
+    fn set_eval_file(&mut self, path: &str) {
+        self.network = None;
+
+        if !path.is_empty() && path != "<empty>" {
+            match Network::load(Path::new(path)) {
+                Ok(network) => {
+                    println!(
+                        "info string Loaded network '{}' for size {} and half komi {}",
+                        path,
+                        network.size(),
+                        network.half_komi()
+                    );
+                    self.network = Some(Arc::new(network));
+                }
+                Err(err) => {
+                    eprintln!("Failed to load network '{}': {}", path, err);
+                    println!("info string Using handcrafted eval");
+                }
+            }
+        }
+
+        self.searcher.set_network(self.network.clone());
+    }
+
+    fn set_book_file(&mut self, path: &str) {
+        let mut book = None;
+
+        if !path.is_empty() && path != "<empty>" {
+            match Book::load(Path::new(path)) {
+                Ok(loaded) => {
+                    println!(
+                        "info string Loaded book '{}' with {} moves for size {} and half komi {}",
+                        path,
+                        loaded.len(),
+                        loaded.size(),
+                        loaded.half_komi()
+                    );
+                    book = Some(Arc::new(loaded));
+                }
+                Err(err) => eprintln!("Failed to load book '{}': {}", path, err),
+            }
+        }
+
+        self.searcher.set_book(book);
+    }
+
+    // d [stacks] [color]
+    fn handle_d(&self, args: &[&str]) {
+        let mut show_stacks = false;
+        let mut color = false;
+
+        for &arg in args {
+            match arg {
+                "stacks" => show_stacks = true,
+                "color" | "colour" => color = true,
+                unknown => {
+                    eprintln!("Unknown argument '{}'", unknown);
+                    return;
+                }
+            }
+        }
+
+        print!("{}", self.pos.render(color));
+        println!();
+
+        println!(
+            "Side to move: {}, ply {} (move {})",
+            self.pos.stm().raw() + 1,
+            self.pos.ply(),
+            self.pos.ply() / 2 + 1
+        );
+
+        for player in [Player::P1, Player::P2] {
+            println!(
+                "Reserves (P{}): {} flats, {} caps",
+                player.raw() + 1,
+                self.pos.flats_in_hand(player),
+                self.pos.caps_in_hand(player)
+            );
+        }
+
+        if show_stacks {
+            for sq in self.pos.occ() {
+                if self.pos.stacks().height(sq) > 1 {
+                    println!("Stack {}: {}", sq, self.pos.stack_tps(sq));
+                }
+            }
+        }
+
+        println!();
+
         println!("TPS: {}", self.pos.tps());
         println!("Key: {:016x}", self.pos.key());
 
-        let static_eval = static_eval(&self.pos);
+        let (static_eval, source) = match self.network.as_deref() {
+            Some(network) if network.supports(&self.pos) => (network.evaluate(&self.pos), "network"),
+            _ => (static_eval(&self.pos), "handcrafted"),
+        };
+
         let static_eval = match self.pos.stm() {
             Player::P1 => static_eval,
             Player::P2 => -static_eval,
         };
 
-        println!("Static eval (P1-relative): {:+.2}", (static_eval as f64) / 100.0);
+        println!(
+            "Static eval (P1-relative, {}): {:+.2}",
+            source,
+            (static_eval as f64) / 100.0
+        );
+
+        for player in [Player::P1, Player::P2] {
+            if let Some(road) = self.pos.road(player) {
+                print!("Road ({:?}, {}):", player, road.orientation);
+                for sq in road.squares {
+                    print!(" {}", sq);
+                }
+                println!();
+            }
+        }
+    }
+
+    fn handle_eval(&mut self) {
+        if self.searcher.is_searching() {
+            eprintln!("Search running");
+            return;
+        }
+
+        print_trace(&self.pos);
+
+        if let Some(network) = self.network.as_deref()
+            && network.supports(&self.pos)
+        {
+            println!(
+                "Static eval (stm-relative, network): {}",
+                format_flats(network.evaluate(&self.pos))
+            );
+        }
+
+        println!(
+            "Correction (stm-relative): {}",
+            format_flats(self.searcher.correction(&self.pos))
+        );
+    }
+
+    fn handle_bench(&self, args: &[&str]) {
+        if self.searcher.is_searching() {
+            eprintln!("Search running");
+            return;
+        }
+
+        let depth = match args.first() {
+            Some(depth) => match depth.parse() {
+                Ok(depth) if depth > 0 => depth,
+                _ => {
+                    eprintln!("Invalid depth '{}'", depth);
+                    return;
+                }
+            },
+            None => bench::DEFAULT_BENCH_DEPTH,
+        };
+
+        bench::run_bench(depth);
     }
 
     fn handle_perft(&self, args: &[&str]) {
         if args.is_empty() {
             eprintln!("Missing depth");
+            return;
         }
 
         let depth = match args[0].parse() {
@@ -492,12 +804,17 @@ impl TeiHandler {
             }
         };
 
-        println!("{}", perft(&self.pos, depth));
+        let Some(options) = perft::parse_options(&args[1..]) else {
+            return;
+        };
+
+        print_perft(&self.pos, depth, &options);
     }
 
     fn handle_splitperft(&self, args: &[&str]) {
         if args.is_empty() {
             eprintln!("Missing depth");
+            return;
         }
 
         let depth = match args[0].parse() {
@@ -508,7 +825,70 @@ impl TeiHandler {
             }
         };
 
-        split_perft(&self.pos, depth);
+        let Some(options) = perft::parse_options(&args[1..]) else {
+            return;
+        };
+
+        split_perft(&self.pos, depth, &options);
+    }
+
+    fn handle_perftcompare(&self, args: &[&str]) {
+        if args.is_empty() {
+            eprintln!("Missing depth");
+            return;
+        }
+
+        let depth = match args[0].parse() {
+            Ok(depth) => depth,
+            Err(_) => {
+                eprintln!("Invalid depth '{}'", args[0]);
+                return;
+            }
+        };
+
+        compare_perft(&self.pos, depth);
+    }
+
+    fn handle_savehash(&self, args: &[&str]) {
+        if self.searcher.is_searching() {
+            eprintln!("Search running");
+            return;
+        }
+
+        if args.is_empty() {
+            eprintln!("Missing path");
+            return;
+        }
+
+        let path = args.join(" ");
+
+        match self.searcher.save_tt(Path::new(&path)) {
+            Ok(()) => println!("info string Saved hash to '{}'", path),
+            Err(err) => eprintln!("Failed to save hash to '{}': {}", path, err),
+        }
+    }
+
+    fn handle_loadhash(&mut self, args: &[&str]) {
+        if self.searcher.is_searching() {
+            eprintln!("Search running");
+            return;
+        }
+
+        if args.is_empty() {
+            eprintln!("Missing path");
+            return;
+        }
+
+        let path = args.join(" ");
+
+        match self.searcher.load_tt(Path::new(&path)) {
+            Ok(()) => println!("info string Loaded hash from '{}'", path),
+            Err(TtFileError::SizeMismatch { file_mib, current_mib }) => eprintln!(
+                "Hash file '{}' is for {} MiB but Hash is {} MiB, set Hash to {} first",
+                path, file_mib, current_mib, file_mib
+            ),
+            Err(err) => eprintln!("Failed to load hash from '{}': {}", path, err),
+        }
     }
 }
 
@@ -0,0 +1,318 @@
+/*
+ * syntaks, a TEI Tak engine
+ * Copyright (c) 2026 Ciekce
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use crate::board::Position;
+use crate::eval::{EvalFeatures, EvalTerm, static_eval};
+use crate::ptn::GameResult;
+use crate::tunable;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::time::Instant;
+
+const DEFAULT_EPOCHS: usize = 2000;
+const DEFAULT_LEARNING_RATE: f64 = 0.5;
+
+const REPORT_INTERVAL: usize = 100;
+
+const ADAM_BETA1: f64 = 0.9;
+const ADAM_BETA2: f64 = 0.999;
+const ADAM_EPSILON: f64 = 1e-8;
+
+struct Entry {
+    // P1-relative, already divided by each term's divisor
+    features: [f64; EvalTerm::COUNT],
+    // expected P1 score in [0, 1]
+    target: f64,
+}
+
+enum Label {
+    Result(f64),
+    // P1-relative centiflats, turned into a target once the scaling is known
+    Score(f64),
+}
+
+#[must_use]
+fn parse_label(label: &str) -> Option<Label> {
+    if let Ok(result) = label.parse::<GameResult>() {
+        let target = match result {
+            GameResult::RoadWin(player) | GameResult::FlatWin(player) | GameResult::OtherWin(player) => {
+                if player.idx() == 0 { 1.0 } else { 0.0 }
+            }
+            GameResult::Draw => 0.5,
+        };
+        return Some(Label::Result(target));
+    }
+
+    label
+        .parse::<f64>()
+        .ok()
+        .filter(|score| score.is_finite())
+        .map(Label::Score)
+}
+
+#[must_use]
+fn sigmoid(eval: f64, k: f64) -> f64 {
+    1.0 / (1.0 + (-k * eval / 400.0).exp())
+}
+
+#[must_use]
+fn eval_entry(features: &[f64; EvalTerm::COUNT], weights: &[f64; EvalTerm::COUNT]) -> f64 {
+    features.iter().zip(weights.iter()).map(|(f, w)| f * w).sum()
+}
+
+#[must_use]
+fn mean_error(entries: &[Entry], weights: &[f64; EvalTerm::COUNT], k: f64) -> f64 {
+    let total: f64 = entries
+        .iter()
+        .map(|entry| {
+            let diff = sigmoid(eval_entry(&entry.features, weights), k) - entry.target;
+            diff * diff
+        })
+        .sum();
+
+    total / entries.len() as f64
+}
+
+// ternary search for the scaling that best fits the current weights
+#[must_use]
+fn fit_k(entries: &[Entry], weights: &[f64; EvalTerm::COUNT]) -> f64 {
+    let mut lo = 0.0;
+    let mut hi = 10.0;
+
+    for _ in 0..100 {
+        let a = lo + (hi - lo) / 3.0;
+        let b = hi - (hi - lo) / 3.0;
+
+        if mean_error(entries, weights, a) < mean_error(entries, weights, b) {
+            hi = b;
+        } else {
+            lo = a;
+        }
+    }
+
+    (lo + hi) / 2.0
+}
+
+// lines are a TPS followed by a P1-relative label, either a game result or a score in centiflats.
+// datagen output has both a score and a result, the result is used
+fn load_entries(path: &str, half_komi: u32) -> Result<Vec<(Position, Label)>, String> {
+    let file = File::open(path).map_err(|err| format!("Failed to open '{}': {}", path, err))?;
+
+    let mut result = Vec::new();
+
+    for (line_idx, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|err| format!("Failed to read '{}': {}", path, err))?;
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let parts: Vec<_> = line.split_ascii_whitespace().collect();
+
+        if parts.len() != 4 && parts.len() != 5 {
+            return Err(format!("Line {}: expected a TPS and a label", line_idx + 1));
+        }
+
+        let mut pos = Position::from_tps_parts(&parts[..3])
+            .map_err(|err| format!("Line {}: invalid TPS: {:?}", line_idx + 1, err))?;
+        pos.set_half_komi(half_komi);
+
+        let label_str = parts[parts.len() - 1];
+        let label =
+            parse_label(label_str).ok_or_else(|| format!("Line {}: invalid label '{}'", line_idx + 1, label_str))?;
+
+        result.push((pos, label));
+    }
+
+    Ok(result)
+}
+
+// in the format of the `tunables!` entries in tunable.rs, ready to be pasted back in
+fn print_weights(weights: &[f64; EvalTerm::COUNT]) {
+    for term in EvalTerm::ALL {
+        let weight = weights[term.idx()].round() as i32;
+        println!("    {}", tunable::format_entry(term.name(), weight).unwrap());
+    }
+}
+
+// tune <file> [epochs <n>] [lr <rate>] [halfkomi <n>]
+pub fn run(args: &[String]) -> bool {
+    let Some(path) = args.first() else {
+        eprintln!("Missing data file");
+        return false;
+    };
+
+    let mut epochs = DEFAULT_EPOCHS;
+    let mut learning_rate = DEFAULT_LEARNING_RATE;
+    let mut half_komi = Position::DEFAULT_HALF_KOMI;
+
+    let mut i = 1;
+    while i < args.len() {
+        let Some(value) = args.get(i + 1) else {
+            eprintln!("Missing value for '{}'", args[i]);
+            return false;
+        };
+
+        let valid = match args[i].as_str() {
+            "epochs" => value.parse().map(|value| epochs = value).is_ok(),
+            "lr" => value
+                .parse::<f64>()
+                .ok()
+                .filter(|&value| value > 0.0)
+                .map(|value| learning_rate = value)
+                .is_some(),
+            "halfkomi" => value
+                .parse::<u32>()
+                .ok()
+                .filter(|&value| value <= Position::MAX_HALF_KOMI)
+                .map(|value| half_komi = value)
+                .is_some(),
+            unknown => {
+                eprintln!("Unknown option '{}'", unknown);
+                return false;
+            }
+        };
+
+        if !valid {
+            eprintln!("Invalid value '{}' for '{}'", value, args[i]);
+            return false;
+        }
+
+        i += 2;
+    }
+
+    let positions = match load_entries(path, half_komi) {
+        Ok(positions) => positions,
+        Err(err) => {
+            eprintln!("{}", err);
+            return false;
+        }
+    };
+
+    if positions.is_empty() {
+        eprintln!("No positions in '{}'", path);
+        return false;
+    }
+
+    println!("Loaded {} positions", positions.len());
+
+    let mut weights = [0.0; EvalTerm::COUNT];
+    for term in EvalTerm::ALL {
+        weights[term.idx()] = term.weight() as f64;
+    }
+
+    let mut entries: Vec<_> = positions
+        .iter()
+        .map(|(pos, _)| {
+            let counts = EvalFeatures::new(pos).counts;
+
+            let mut features = [0.0; EvalTerm::COUNT];
+            for term in EvalTerm::ALL {
+                features[term.idx()] = counts[term.idx()] as f64 / term.divisor() as f64;
+            }
+
+            // the features are linear apart from integer rounding
+            debug_assert!(
+                (eval_entry(&features, &weights) - (static_eval(pos) * pos.stm().sign()) as f64).abs() <= 1.0
+            );
+
+            Entry { features, target: 0.0 }
+        })
+        .collect();
+
+    // scores are converted with the scaling that best fits game results,
+    // falling back to the default eval's fit if there are none
+    let result_entries: Vec<_> = entries
+        .iter()
+        .zip(positions.iter())
+        .filter_map(|(entry, (_, label))| match label {
+            Label::Result(target) => Some(Entry {
+                features: entry.features,
+                target: *target,
+            }),
+            Label::Score(_) => None,
+        })
+        .collect();
+
+    let k = if result_entries.is_empty() {
+        1.0
+    } else {
+        fit_k(&result_entries, &weights)
+    };
+
+    for (entry, (_, label)) in entries.iter_mut().zip(positions.iter()) {
+        entry.target = match *label {
+            Label::Result(target) => target,
+            Label::Score(score) => sigmoid(score, k),
+        };
+    }
+
+    println!("K: {:.6}", k);
+    println!("Initial error: {:.8}", mean_error(&entries, &weights, k));
+
+    let start = Instant::now();
+
+    let mut momentum = [0.0; EvalTerm::COUNT];
+    let mut velocity = [0.0; EvalTerm::COUNT];
+
+    for epoch in 1..=epochs {
+        let mut gradient = [0.0; EvalTerm::COUNT];
+
+        for entry in entries.iter() {
+            let p = sigmoid(eval_entry(&entry.features, &weights), k);
+            // d/d(eval) of (p - target)^2
+            let factor = 2.0 * (p - entry.target) * p * (1.0 - p) * k / 400.0;
+
+            for (grad, feature) in gradient.iter_mut().zip(entry.features.iter()) {
+                *grad += factor * feature;
+            }
+        }
+
+        for idx in 0..EvalTerm::COUNT {
+            let grad = gradient[idx] / entries.len() as f64;
+
+            momentum[idx] = ADAM_BETA1 * momentum[idx] + (1.0 - ADAM_BETA1) * grad;
+            velocity[idx] = ADAM_BETA2 * velocity[idx] + (1.0 - ADAM_BETA2) * grad * grad;
+
+            let m = momentum[idx] / (1.0 - ADAM_BETA1.powi(epoch as i32));
+            let v = velocity[idx] / (1.0 - ADAM_BETA2.powi(epoch as i32));
+
+            weights[idx] -= learning_rate * m / (v.sqrt() + ADAM_EPSILON);
+        }
+
+        if epoch % REPORT_INTERVAL == 0 || epoch == epochs {
+            println!(
+                "Epoch {} error {:.8} time {:.1}s",
+                epoch,
+                mean_error(&entries, &weights, k),
+                start.elapsed().as_secs_f64()
+            );
+        }
+    }
+
+    println!();
+    print_weights(&weights);
+
+    true
+}
@@ -22,7 +22,9 @@
  */
 
 use crate::board::FlatCountOutcome;
+use crate::eval::static_eval;
 use crate::limit::Limits;
+use crate::nnue::{Network, NnueState};
 use crate::node_counter::NodeCounter;
 use crate::tei::TeiOptions;
 use crate::ttable::{DEFAULT_TT_SIZE_MIB, TranspositionTable};
@@ -34,8 +36,9 @@ use crate::{
     search::{MAX_DEPTH, SCORE_INF, Score},
     takmove::Move,
 };
-use std::sync::Arc;
+use std::cmp::Reverse;
 use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::Instant;
 
 pub struct SearcherCount {
@@ -93,9 +96,14 @@ impl SearcherCount {
 pub struct SharedContext {
     pub tt: TranspositionTable,
     pub options: TeiOptions,
+    pub network: Option<Arc<Network>>,
+    // suppresses all output, the outcome is only kept in `result`
+    pub quiet: bool,
+    result: Mutex<Option<SearchResult>>,
     start_time: Instant,
     limits: Limits,
     stopped: AtomicBool,
+    pondering: AtomicU32,
     counter: Arc<SearcherCount>,
     nodes: NodeCounter,
 }
@@ -106,9 +114,13 @@ impl SharedContext {
         Self {
             tt: TranspositionTable::new(DEFAULT_TT_SIZE_MIB),
             options: Default::default(),
+            network: None,
+            quiet: false,
+            result: Mutex::new(None),
             start_time: time,
             limits: Limits::new(time),
             stopped: AtomicBool::new(false),
+            pondering: AtomicU32::new(0),
             counter: Arc::new(SearcherCount::new()),
             nodes: NodeCounter::new(1),
         }
@@ -118,11 +130,14 @@ impl SharedContext {
         self.nodes.resize(threads as usize);
     }
 
-    pub fn init_search(&mut self, options: &TeiOptions, start_time: Instant, limits: Limits) {
+    pub fn init_search(&mut self, options: &TeiOptions, start_time: Instant, limits: Limits, ponder: bool) {
         self.options = *options;
         self.start_time = start_time;
         self.limits = limits;
         self.stopped.store(false, Ordering::Relaxed);
+        self.pondering.store(ponder as u32, Ordering::Relaxed);
+        *self.result.get_mut().unwrap() = None;
+        self.tt.new_search();
         self.counter.start();
         self.nodes.reset();
     }
@@ -136,6 +151,10 @@ impl SharedContext {
     }
 
     pub fn check_stop_soft(&self, nodes: usize, best_move_nodes_fraction: f64) -> bool {
+        if self.is_pondering() {
+            return false;
+        }
+
         if self.limits.should_stop_soft(nodes, best_move_nodes_fraction) {
             self.stopped.store(true, Ordering::Relaxed);
             return true;
@@ -145,6 +164,10 @@ impl SharedContext {
     }
 
     pub fn check_stop_hard(&self, nodes: usize) -> bool {
+        if self.is_pondering() {
+            return false;
+        }
+
         if self.limits.should_stop_hard(nodes) {
             self.stopped.store(true, Ordering::Relaxed);
             return true;
@@ -171,6 +194,33 @@ impl SharedContext {
     pub fn has_stopped(&self) -> bool {
         self.stopped.load(Ordering::Relaxed)
     }
+
+    #[must_use]
+    pub fn is_pondering(&self) -> bool {
+        self.pondering.load(Ordering::Acquire) != 0
+    }
+
+    // limits apply from here on, measured from the original `go`
+    pub fn end_ponder(&self) {
+        self.pondering.store(0, Ordering::Release);
+        atomic_wait::wake_all(&self.pondering);
+    }
+
+    pub fn set_result(&self, result: SearchResult) {
+        *self.result.lock().unwrap() = Some(result);
+    }
+
+    #[must_use]
+    pub fn result(&self) -> Option<SearchResult> {
+        *self.result.lock().unwrap()
+    }
+
+    // bestmove must not be sent until the ponder search is resolved
+    pub fn wait_for_ponder_end(&self) {
+        while self.is_pondering() {
+            atomic_wait::wait(&self.pondering, 1);
+        }
+    }
 }
 
 pub type PvList = arrayvec::ArrayVec<Move, { MAX_DEPTH as usize }>;
@@ -181,6 +231,15 @@ pub fn update_pv(pv: &mut PvList, mv: Move, child: &PvList) {
     pv.try_extend_from_slice(child).unwrap();
 }
 
+#[derive(Copy, Clone, Debug)]
+pub struct SearchResult {
+    pub mv: Move,
+    // from the side to move's perspective
+    pub score: Score,
+    pub depth: i32,
+    pub nodes: usize,
+}
+
 #[derive(Clone, Debug)]
 pub struct RootMove {
     pub score: Score,
@@ -240,6 +299,7 @@ pub struct ThreadData {
     pub corrhist: Box<CorrectionHistory>,
     pub history: Box<History>,
     pub killers: [KillerTable; MAX_DEPTH as usize],
+    pub nnue: NnueState,
     pub shared: Option<Arc<SharedContext>>,
 }
 
@@ -256,6 +316,7 @@ impl ThreadData {
             corrhist: CorrectionHistory::boxed(),
             history: History::boxed(),
             killers: [Default::default(); MAX_DEPTH as usize],
+            nnue: NnueState::new(),
             shared: None,
         }
     }
@@ -292,27 +353,46 @@ impl ThreadData {
     }
 
     pub fn sort_searched_root_moves(&mut self) {
-        self.root_moves[..=self.pv_idx].sort_by(|a, b| b.score.cmp(&a.score));
+        self.root_moves[..=self.pv_idx].sort_by_key(|root_move| Reverse(root_move.score));
     }
 
     pub fn sort_remaining_root_moves(&mut self) {
-        self.root_moves[self.pv_idx..].sort_by(|a, b| b.score.cmp(&a.score));
+        self.root_moves[self.pv_idx..].sort_by_key(|root_move| Reverse(root_move.score));
     }
 
     pub fn apply_move(&mut self, ply: i32, pos: &Position, mv: Move) -> Position {
+        let new_pos = pos.apply_move(mv);
+        self.push_move(ply, pos, &new_pos, mv);
+        new_pos
+    }
+
+    // for moves the caller has already applied to `pos` itself
+    pub fn push_move(&mut self, ply: i32, pos: &Position, new_pos: &Position, mv: Move) {
         self.key_history.push(pos.key());
         self.stack[ply as usize].mv = Some(mv);
-        pos.apply_move(mv)
+        self.nnue.push_move(pos, new_pos, mv);
     }
 
     pub fn apply_nullmove(&mut self, ply: i32, pos: &Position) -> Position {
         self.key_history.push(pos.key());
         self.stack[ply as usize].mv = None;
+        self.nnue.push_nullmove();
         pos.apply_nullmove()
     }
 
     pub fn pop_move(&mut self) {
         self.key_history.pop();
+        self.nnue.pop();
+    }
+
+    // falls back to the handcrafted eval without a network for this size and komi
+    #[must_use]
+    pub fn evaluate(&self, pos: &Position) -> Score {
+        if self.nnue.is_active() {
+            self.nnue.evaluate(pos)
+        } else {
+            static_eval(pos)
+        }
     }
 
     fn is_drawn_by_repetition(&self, curr: u64, ply: i32) -> bool {
@@ -368,17 +448,6 @@ impl ThreadData {
         None
     }
 
-    #[must_use]
-    pub fn get_root_move(&self, mv: Move) -> &RootMove {
-        for root_move in self.root_moves[self.pv_idx..].iter() {
-            if root_move.pv[0] == mv {
-                return root_move;
-            }
-        }
-
-        unreachable!();
-    }
-
     #[must_use]
     pub fn get_root_move_mut(&mut self, mv: Move) -> &mut RootMove {
         for root_move in self.root_moves[self.pv_idx..].iter_mut() {
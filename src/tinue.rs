@@ -0,0 +1,388 @@
+/*
+ * syntaks, a TEI Tak engine
+ * Copyright (c) 2026 Ciekce
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use crate::board::{FlatCountOutcome, Position};
+use crate::movegen::generate_moves;
+use crate::takmove::Move;
+use crate::thread::SharedContext;
+use std::time::Instant;
+
+pub const DEFAULT_TINUE_DEPTH: i32 = 5;
+pub const MAX_TINUE_DEPTH: i32 = 32;
+
+const PROOF_TABLE_SIZE_MIB: usize = 16;
+
+#[derive(Copy, Clone, Debug)]
+enum Proof {
+    // proven within `depth` attacker moves, with the proving move at attacker nodes
+    Proven(Option<Move>),
+    // disproven within `depth` attacker moves
+    Disproven,
+}
+
+#[derive(Copy, Clone, Debug)]
+struct ProofEntry {
+    key: u64,
+    depth: i32,
+    proof: Proof,
+}
+
+// always-replace, with full keys so that a collision cannot produce a false proof
+struct ProofTable {
+    entries: Vec<Option<ProofEntry>>,
+}
+
+impl ProofTable {
+    #[must_use]
+    fn new(size_mib: usize) -> Self {
+        let count = size_mib * 1024 * 1024 / size_of::<Option<ProofEntry>>();
+        Self {
+            entries: vec![None; count],
+        }
+    }
+
+    fn clear(&mut self) {
+        self.entries.fill(None);
+    }
+
+    #[must_use]
+    fn index(&self, key: u64) -> usize {
+        ((key as u128 * self.entries.len() as u128) >> 64) as usize
+    }
+
+    #[must_use]
+    fn probe(&self, key: u64) -> Option<ProofEntry> {
+        self.entries[self.index(key)].filter(|entry| entry.key == key)
+    }
+
+    fn store(&mut self, key: u64, depth: i32, proof: Proof) {
+        let idx = self.index(key);
+        self.entries[idx] = Some(ProofEntry { key, depth, proof });
+    }
+}
+
+// Threat-space search for tinue: the attacker may only play moves that win outright or
+// leave a road threat on the board, while the defender may play anything. Depth is
+// counted in attacker moves, including the final road-completing move.
+//
+// A proof within some depth also holds at any greater depth, and a disproof at any lesser one.
+//
+// Repetitions are not considered, as a defender cannot escape a continuous
+// sequence of road threats by repeating positions.
+pub struct TinueSolver<'a> {
+    shared: &'a SharedContext,
+    proofs: ProofTable,
+    movelists: Vec<Vec<Move>>,
+    nodes: usize,
+    // set once the search is stopped, after which nothing is proven or stored
+    aborted: bool,
+}
+
+impl<'a> TinueSolver<'a> {
+    #[must_use]
+    pub fn new(shared: &'a SharedContext) -> Self {
+        Self {
+            shared,
+            proofs: ProofTable::new(PROOF_TABLE_SIZE_MIB),
+            movelists: Vec::new(),
+            nodes: 0,
+            aborted: false,
+        }
+    }
+
+    // iteratively deepens up to `max_depth`, returning the proof line for the shortest tinue found.
+    // None if there is no tinue within `max_depth`, or the search was stopped before finding one
+    pub fn solve(&mut self, pos: &Position, max_depth: i32) -> Option<Vec<Move>> {
+        let start = Instant::now();
+
+        self.nodes = 0;
+        self.aborted = false;
+        self.proofs.clear();
+
+        for depth in 1..=max_depth {
+            let proven = self.prove_attack(pos, depth, 0);
+
+            if self.aborted {
+                return None;
+            }
+
+            let time = start.elapsed().as_secs_f64();
+            let nps = (self.nodes as f64 / time) as usize;
+
+            print!(
+                "info depth {} time {} nodes {} nps {}",
+                depth,
+                (time * 1000.0) as usize,
+                self.nodes,
+                nps
+            );
+
+            if let Some(mv) = proven {
+                let mut line = self.proof_line(pos, depth);
+
+                // stopped while following the line
+                if line.is_empty() {
+                    line.push(mv);
+                }
+
+                print!(" score mate {} pv", line.len().div_ceil(2));
+                for mv in line.iter() {
+                    print!(" {}", mv);
+                }
+                println!();
+
+                return Some(line);
+            }
+
+            println!();
+        }
+
+        None
+    }
+
+    fn take_moves(&mut self, pos: &Position, ply: usize) -> Vec<Move> {
+        if self.movelists.len() <= ply {
+            self.movelists.resize_with(ply + 1, || Vec::with_capacity(256));
+        }
+
+        let mut moves = std::mem::take(&mut self.movelists[ply]);
+        generate_moves(&mut moves, pos);
+        moves
+    }
+
+    fn find_road_win(&mut self, pos: &Position, ply: usize) -> Option<Move> {
+        let player = pos.stm();
+        let moves = self.take_moves(pos, ply);
+
+        let mut result = None;
+
+        for &mv in moves.iter() {
+            self.nodes += 1;
+            if pos.apply_move(mv).has_road(player) {
+                result = Some(mv);
+                break;
+            }
+        }
+
+        self.movelists[ply] = moves;
+
+        result
+    }
+
+    // whether the player who just moved would win with another move
+    fn has_threat(&mut self, pos: &Position) -> bool {
+        self.nodes += 1;
+        !pos.road_threats(pos.stm().flip()).is_empty()
+    }
+
+    fn check_stop(&mut self) -> bool {
+        self.aborted |= self.shared.has_stopped();
+        self.aborted
+    }
+
+    fn prove_attack(&mut self, pos: &Position, depth: i32, ply: usize) -> Option<Move> {
+        if self.check_stop() {
+            return None;
+        }
+
+        if let Some(mv) = self.find_road_win(pos, ply) {
+            return Some(mv);
+        }
+
+        if depth <= 1 {
+            return None;
+        }
+
+        if let Some(entry) = self.proofs.probe(pos.key()) {
+            match entry.proof {
+                Proof::Proven(Some(mv)) if entry.depth <= depth && pos.is_legal(mv) => return Some(mv),
+                Proof::Disproven if entry.depth >= depth => return None,
+                _ => {}
+            }
+        }
+
+        let attacker = pos.stm();
+        let moves = self.take_moves(pos, ply);
+
+        let mut result = None;
+
+        for &mv in moves.iter() {
+            self.nodes += 1;
+            let child = pos.apply_move(mv);
+
+            if child.has_road(attacker.flip()) || !matches!(child.count_flats(), FlatCountOutcome::None) {
+                continue;
+            }
+
+            if !self.has_threat(&child) {
+                continue;
+            }
+
+            if self.refutes_all_defences(&child, depth - 1, ply + 1) {
+                result = Some(mv);
+                break;
+            }
+        }
+
+        self.movelists[ply] = moves;
+
+        if self.aborted {
+            return None;
+        }
+
+        let proof = if result.is_some() {
+            Proof::Proven(result)
+        } else {
+            Proof::Disproven
+        };
+        self.proofs.store(pos.key(), depth, proof);
+
+        result
+    }
+
+    fn refutes_all_defences(&mut self, pos: &Position, depth: i32, ply: usize) -> bool {
+        if self.check_stop() {
+            return false;
+        }
+
+        let defender = pos.stm();
+
+        if self.find_road_win(pos, ply).is_some() {
+            return false;
+        }
+
+        if let Some(entry) = self.proofs.probe(pos.key()) {
+            match entry.proof {
+                Proof::Proven(_) if entry.depth <= depth => return true,
+                Proof::Disproven if entry.depth >= depth => return false,
+                _ => {}
+            }
+        }
+
+        let moves = self.take_moves(pos, ply);
+
+        let mut proven = true;
+
+        for &mv in moves.iter() {
+            self.nodes += 1;
+            let child = pos.apply_move(mv);
+
+            // completing both roads at once wins for the mover
+            if child.has_road(defender) {
+                proven = false;
+                break;
+            }
+
+            // the defender completed the attacker's road themselves
+            if child.has_road(defender.flip()) {
+                continue;
+            }
+
+            if !matches!(child.count_flats(), FlatCountOutcome::None)
+                || self.prove_attack(&child, depth, ply + 1).is_none()
+            {
+                proven = false;
+                break;
+            }
+        }
+
+        self.movelists[ply] = moves;
+
+        if self.aborted {
+            return false;
+        }
+
+        let proof = if proven { Proof::Proven(None) } else { Proof::Disproven };
+        self.proofs.store(pos.key(), depth, proof);
+
+        proven
+    }
+
+    #[must_use]
+    fn shortest_proof(&mut self, pos: &Position, max_depth: i32) -> Option<(i32, Move)> {
+        for depth in 1..=max_depth {
+            if let Some(mv) = self.prove_attack(pos, depth, 0) {
+                return Some((depth, mv));
+            }
+        }
+
+        None
+    }
+
+    // follows the fastest attacking moves and the most resilient defences
+    #[must_use]
+    fn proof_line(&mut self, pos: &Position, depth: i32) -> Vec<Move> {
+        let attacker = pos.stm();
+
+        let mut line = Vec::new();
+        let mut pos = *pos;
+        let mut depth = depth;
+
+        while let Some((_, mv)) = self.shortest_proof(&pos, depth) {
+            line.push(mv);
+            pos = pos.apply_move(mv);
+
+            if pos.has_road(attacker) {
+                break;
+            }
+
+            let mut defences = Vec::with_capacity(256);
+            generate_moves(&mut defences, &pos);
+
+            let mut best_defence = None;
+
+            for mv in defences {
+                let child = pos.apply_move(mv);
+
+                let remaining = if child.has_road(attacker) && !child.has_road(attacker.flip()) {
+                    0
+                } else {
+                    match self.shortest_proof(&child, depth - 1) {
+                        Some((remaining, _)) => remaining,
+                        // should be unreachable, barring TT collisions
+                        None => continue,
+                    }
+                };
+
+                if best_defence.is_none_or(|(best, _)| remaining > best) {
+                    best_defence = Some((remaining, mv));
+                }
+            }
+
+            let Some((remaining, defence)) = best_defence else {
+                break;
+            };
+
+            line.push(defence);
+            pos = pos.apply_move(defence);
+
+            if remaining == 0 {
+                break;
+            }
+
+            depth = remaining;
+        }
+
+        line
+    }
+}
@@ -21,10 +21,17 @@
  * SOFTWARE.
  */
 
+use crate::keys::KEY_SCHEME_FINGERPRINT;
 use crate::search::{SCORE_WIN, Score};
 use crate::takmove::Move;
+use crate::util::checksum::Checksum;
+use crate::util::le::{read_u32, read_u64};
 use std::arch::x86_64::{_MM_HINT_T0, _mm_prefetch};
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
 use std::mem::MaybeUninit;
+use std::path::Path;
 use std::sync::atomic::{AtomicU64, Ordering};
 
 pub const DEFAULT_TT_SIZE_MIB: usize = 64;
@@ -38,6 +45,17 @@ pub enum TtFlag {
     Exact,
 }
 
+const FLAG_BITS: u32 = 2;
+const FLAG_MASK: u8 = (1 << FLAG_BITS) - 1;
+
+const AGE_CYCLE: u8 = 1 << (u8::BITS - FLAG_BITS);
+
+// depth an entry loses per search it has survived, for replacement purposes
+const AGE_DEPTH_PENALTY: i32 = 8;
+
+// same-position entries are kept unless the new one is exact or nearly as deep
+const SAME_KEY_DEPTH_MARGIN: i32 = 4;
+
 #[derive(Copy, Clone, Debug, Default)]
 #[repr(C)]
 struct Entry {
@@ -45,7 +63,46 @@ struct Entry {
     score: i16,
     mv: Option<Move>,
     depth: u8,
-    flag: Option<TtFlag>,
+    flag_age: u8,
+}
+
+impl Entry {
+    #[must_use]
+    fn flag(&self) -> Option<TtFlag> {
+        match self.flag_age & FLAG_MASK {
+            0 => None,
+            1 => Some(TtFlag::UpperBound),
+            2 => Some(TtFlag::LowerBound),
+            3 => Some(TtFlag::Exact),
+            _ => unreachable!(),
+        }
+    }
+
+    #[must_use]
+    fn age(&self) -> u8 {
+        self.flag_age >> FLAG_BITS
+    }
+
+    fn set_flag_age(&mut self, flag: TtFlag, age: u8) {
+        debug_assert!(age < AGE_CYCLE);
+        self.flag_age = (age << FLAG_BITS) | flag as u8;
+    }
+
+    fn set_age(&mut self, age: u8) {
+        debug_assert!(age < AGE_CYCLE);
+        self.flag_age = (age << FLAG_BITS) | (self.flag_age & FLAG_MASK);
+    }
+
+    #[must_use]
+    fn is_empty(&self) -> bool {
+        self.flag_age & FLAG_MASK == 0
+    }
+
+    // number of searches started since this entry was last written or hit
+    #[must_use]
+    fn relative_age(&self, age: u8) -> i32 {
+        ((AGE_CYCLE + age - self.age()) % AGE_CYCLE) as i32
+    }
 }
 
 #[derive(Debug, Default)]
@@ -64,6 +121,65 @@ impl EntryStorage {
         let value = unsafe { std::mem::transmute::<Entry, u64>(entry) };
         self.storage.store(value, Ordering::Relaxed);
     }
+
+    fn load_raw(&self) -> u64 {
+        self.storage.load(Ordering::Relaxed)
+    }
+
+    // every bit pattern is a valid Entry
+    fn store_raw(&self, value: u64) {
+        self.storage.store(value, Ordering::Relaxed);
+    }
+}
+
+const CLUSTER_SIZE: usize = 8;
+
+// one cache line
+#[derive(Debug, Default)]
+#[repr(C, align(64))]
+struct Cluster {
+    entries: [EntryStorage; CLUSTER_SIZE],
+}
+
+const HASHFULL_SAMPLE_CLUSTERS: usize = 1000 / CLUSTER_SIZE;
+
+const FILE_MAGIC: [u8; 8] = *b"SYNTAKTT";
+// bump whenever the entry layout, key packing, indexing or checksum changes
+const FILE_VERSION: u32 = 2;
+
+#[derive(Debug)]
+pub enum TtFileError {
+    Io(std::io::Error),
+    InvalidMagic,
+    UnsupportedVersion(u32),
+    LayoutMismatch,
+    KeySchemeMismatch,
+    SizeMismatch { file_mib: usize, current_mib: usize },
+    InvalidAge(u8),
+    ChecksumMismatch,
+}
+
+impl From<std::io::Error> for TtFileError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl Display for TtFileError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{}", err),
+            Self::InvalidMagic => write!(f, "not a hash file"),
+            Self::UnsupportedVersion(version) => write!(f, "unsupported version {}", version),
+            Self::LayoutMismatch => write!(f, "entry layout mismatch"),
+            Self::KeySchemeMismatch => write!(f, "saved with different position keys"),
+            Self::SizeMismatch { file_mib, current_mib } => {
+                write!(f, "saved with {} MiB, hash is {} MiB", file_mib, current_mib)
+            }
+            Self::InvalidAge(age) => write!(f, "invalid age {}", age),
+            Self::ChecksumMismatch => write!(f, "checksum mismatch"),
+        }
+    }
 }
 
 #[derive(Copy, Clone, Debug, Default)]
@@ -75,8 +191,8 @@ pub struct ProbedEntry {
 }
 
 #[must_use]
-fn calc_entry_count(size_mib: usize) -> usize {
-    size_mib * 1024 * 1024 / size_of::<Entry>()
+fn calc_cluster_count(size_mib: usize) -> usize {
+    size_mib * 1024 * 1024 / size_of::<Cluster>()
 }
 
 #[must_use]
@@ -133,7 +249,8 @@ unsafe fn clear_threaded<T: Send>(ptr: *mut MaybeUninit<T>, len: usize, threads:
 }
 
 pub struct TranspositionTable {
-    entries: Vec<EntryStorage>,
+    clusters: Vec<Cluster>,
+    age: u8,
 }
 
 impl TranspositionTable {
@@ -142,7 +259,8 @@ impl TranspositionTable {
         assert!(size_mib > 0);
 
         let mut result = Self {
-            entries: Vec::default(),
+            clusters: Vec::default(),
+            age: 0,
         };
 
         result.resize(size_mib, 1);
@@ -155,96 +273,252 @@ impl TranspositionTable {
         assert!(threads > 0);
 
         // ensure the entire old TT is deallocated
-        self.entries = Vec::new();
+        self.clusters = Vec::new();
 
-        let entry_count = calc_entry_count(size_mib);
-        self.entries = Vec::with_capacity(entry_count);
+        let cluster_count = calc_cluster_count(size_mib);
+        self.clusters = Vec::with_capacity(cluster_count);
 
         unsafe {
-            //SAFETY: all-zeroes is a valid bitpattern for EntryStorage, and the range is fully in bounds
-            clear_threaded::<EntryStorage>(self.entries.as_mut_ptr().cast(), entry_count, threads);
+            //SAFETY: all-zeroes is a valid bitpattern for Cluster, and the range is fully in bounds
+            clear_threaded::<Cluster>(self.clusters.as_mut_ptr().cast(), cluster_count, threads);
             //SAFETY: we just initialised these values
-            self.entries.set_len(entry_count);
+            self.clusters.set_len(cluster_count);
         }
+
+        self.age = 0;
+    }
+
+    // called once per search, so that entries from earlier searches become replaceable
+    pub fn new_search(&mut self) {
+        self.age = (self.age + 1) % AGE_CYCLE;
     }
 
     pub fn prefetch(&self, key: u64) {
         #[cfg(target_arch = "x86_64")]
         {
-            let idx = self.calc_index(key);
-            //SAFETY: calc_index() cannot return an out-of-bounds index
-            let entry = unsafe { self.entries.get_unchecked(idx) };
-            let ptr = std::ptr::from_ref(entry).cast();
+            let ptr = std::ptr::from_ref(self.cluster(key)).cast();
             unsafe { _mm_prefetch(ptr, _MM_HINT_T0) };
         }
     }
 
     #[must_use]
     pub fn probe(&self, key: u64, ply: i32) -> (bool, ProbedEntry) {
-        let idx = self.calc_index(key);
         let entry_key = pack_entry_key(key);
 
-        let mut probed = Default::default();
+        let mut probed = ProbedEntry::default();
 
-        //SAFETY: calc_index() cannot return an out-of-bounds index
-        let entry = unsafe { self.entries.get_unchecked(idx) }.load();
+        for storage in self.cluster(key).entries.iter() {
+            let mut entry = storage.load();
 
-        if entry.key != entry_key {
-            return (false, probed);
-        }
+            if entry.key != entry_key || entry.is_empty() {
+                continue;
+            }
 
-        probed.score = score_from_tt(entry.score, ply);
-        probed.mv = entry.mv;
-        probed.depth = entry.depth as i32;
-        probed.flag = entry.flag;
+            if entry.age() != self.age {
+                entry.set_age(self.age);
+                storage.store(entry);
+            }
 
-        (true, probed)
+            probed.score = score_from_tt(entry.score, ply);
+            probed.mv = entry.mv;
+            probed.depth = entry.depth as i32;
+            probed.flag = entry.flag();
+
+            return (true, probed);
+        }
+
+        (false, probed)
     }
 
     pub fn store(&self, key: u64, score: Score, mv: Option<Move>, depth: i32, ply: i32, flag: TtFlag) {
-        let idx = self.calc_index(key);
         let entry_key = pack_entry_key(key);
 
-        //SAFETY: calc_index() cannot return an out-of-bounds index
-        let storage = unsafe { self.entries.get_unchecked(idx) };
+        let cluster = self.cluster(key);
 
-        let mut entry = storage.load();
+        // prefer this position's own entry or an empty slot,
+        // otherwise the shallowest entry after accounting for age
+        let mut target = &cluster.entries[0];
+        let mut target_entry = target.load();
+        let mut target_value = i32::MAX;
 
-        if mv.is_some() || entry.key != entry_key {
+        for storage in cluster.entries.iter() {
+            let entry = storage.load();
+
+            if entry.key == entry_key || entry.is_empty() {
+                target = storage;
+                target_entry = entry;
+                break;
+            }
+
+            let value = entry.depth as i32 - AGE_DEPTH_PENALTY * entry.relative_age(self.age);
+            if value < target_value {
+                target = storage;
+                target_entry = entry;
+                target_value = value;
+            }
+        }
+
+        let mut entry = target_entry;
+        let same_key = !entry.is_empty() && entry.key == entry_key;
+
+        if mv.is_some() || !same_key {
             entry.mv = mv;
         }
 
-        entry.key = entry_key;
-        entry.score = score_to_tt(score, ply);
-        entry.depth = depth as u8;
-        entry.flag = Some(flag);
+        if !same_key
+            || flag == TtFlag::Exact
+            || depth + SAME_KEY_DEPTH_MARGIN > entry.depth as i32
+            || entry.age() != self.age
+        {
+            entry.key = entry_key;
+            entry.score = score_to_tt(score, ply);
+            entry.depth = depth as u8;
+            entry.set_flag_age(flag, self.age);
+        }
+
+        target.store(entry);
+    }
+
+    #[must_use]
+    pub fn size_mib(&self) -> usize {
+        self.clusters.len() * size_of::<Cluster>() / (1024 * 1024)
+    }
+
+    // header: magic, version, entry and cluster size, key fingerprint, cluster count, age
+    // followed by every entry in order, then a checksum over the entries
+    pub fn save(&self, path: &Path) -> Result<(), TtFileError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        writer.write_all(&FILE_MAGIC)?;
+        writer.write_all(&FILE_VERSION.to_le_bytes())?;
+        writer.write_all(&(size_of::<Entry>() as u32).to_le_bytes())?;
+        writer.write_all(&(CLUSTER_SIZE as u32).to_le_bytes())?;
+        writer.write_all(&KEY_SCHEME_FINGERPRINT.to_le_bytes())?;
+        writer.write_all(&(self.clusters.len() as u64).to_le_bytes())?;
+        writer.write_all(&(self.age as u32).to_le_bytes())?;
+
+        let mut checksum = Checksum::new();
+
+        for cluster in self.clusters.iter() {
+            for storage in cluster.entries.iter() {
+                let value = storage.load_raw();
+                checksum.update(&value.to_le_bytes());
+                writer.write_all(&value.to_le_bytes())?;
+            }
+        }
+
+        writer.write_all(&checksum.value().to_le_bytes())?;
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    // the table must already have the saved size
+    // the header is validated before anything is overwritten, but
+    // if the entries turn out to be truncated or corrupt the table is left cleared
+    pub fn load(&mut self, path: &Path, threads: usize) -> Result<(), TtFileError> {
+        let mut reader = BufReader::new(File::open(path)?);
+
+        let mut magic = [0; FILE_MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+
+        if magic != FILE_MAGIC {
+            return Err(TtFileError::InvalidMagic);
+        }
+
+        let version = read_u32(&mut reader)?;
+        if version != FILE_VERSION {
+            return Err(TtFileError::UnsupportedVersion(version));
+        }
+
+        let entry_size = read_u32(&mut reader)?;
+        let cluster_size = read_u32(&mut reader)?;
 
-        storage.store(entry);
+        if entry_size as usize != size_of::<Entry>() || cluster_size as usize != CLUSTER_SIZE {
+            return Err(TtFileError::LayoutMismatch);
+        }
+
+        if read_u64(&mut reader)? != KEY_SCHEME_FINGERPRINT {
+            return Err(TtFileError::KeySchemeMismatch);
+        }
+
+        let cluster_count = read_u64(&mut reader)?;
+        if cluster_count != self.clusters.len() as u64 {
+            return Err(TtFileError::SizeMismatch {
+                file_mib: (cluster_count as usize).saturating_mul(size_of::<Cluster>()) / (1024 * 1024),
+                current_mib: self.size_mib(),
+            });
+        }
+
+        let age = read_u32(&mut reader)?;
+        if age >= AGE_CYCLE as u32 {
+            return Err(TtFileError::InvalidAge(age as u8));
+        }
+
+        let result = self.load_entries(&mut reader);
+
+        if result.is_ok() {
+            self.age = age as u8;
+        } else {
+            self.clear(threads);
+        }
+
+        result
+    }
+
+    fn load_entries(&mut self, reader: &mut impl Read) -> Result<(), TtFileError> {
+        let mut checksum = Checksum::new();
+
+        for cluster in self.clusters.iter() {
+            for storage in cluster.entries.iter() {
+                let value = read_u64(reader)?;
+                checksum.update(&value.to_le_bytes());
+                storage.store_raw(value);
+            }
+        }
+
+        if read_u64(reader)? != checksum.value() {
+            return Err(TtFileError::ChecksumMismatch);
+        }
+
+        Ok(())
     }
 
     pub fn clear(&mut self, threads: usize) {
         assert!(threads > 0);
 
-        //SAFETY: all-zeroes is a valid bitpattern for EntryStorage, and the range is fully in bounds
-        unsafe { clear_threaded::<EntryStorage>(self.entries.as_mut_ptr().cast(), self.entries.len(), threads) };
+        //SAFETY: all-zeroes is a valid bitpattern for Cluster, and the range is fully in bounds
+        unsafe { clear_threaded::<Cluster>(self.clusters.as_mut_ptr().cast(), self.clusters.len(), threads) };
+
+        self.age = 0;
     }
 
+    // only counts entries written or hit during the current search
     #[must_use]
     pub fn estimate_full_permille(&self) -> usize {
         let mut filled = 0;
 
-        for storage in self.entries[0..1000].iter() {
-            let entry = storage.load();
-            if entry.flag.is_some() {
-                filled += 1;
+        for cluster in self.clusters[0..HASHFULL_SAMPLE_CLUSTERS].iter() {
+            for storage in cluster.entries.iter() {
+                let entry = storage.load();
+                if !entry.is_empty() && entry.age() == self.age {
+                    filled += 1;
+                }
             }
         }
 
-        filled
+        filled * 1000 / (HASHFULL_SAMPLE_CLUSTERS * CLUSTER_SIZE)
+    }
+
+    #[must_use]
+    fn cluster(&self, key: u64) -> &Cluster {
+        let idx = self.calc_index(key);
+        //SAFETY: calc_index() cannot return an out-of-bounds index
+        unsafe { self.clusters.get_unchecked(idx) }
     }
 
     #[must_use]
     fn calc_index(&self, key: u64) -> usize {
-        ((key as u128 * self.entries.len() as u128) >> 64) as usize
+        ((key as u128 * self.clusters.len() as u128) >> 64) as usize
     }
 }
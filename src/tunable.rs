@@ -0,0 +1,185 @@
+/*
+ * syntaks, a TEI Tak engine
+ * Copyright (c) 2026 Ciekce
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+// every tunable constant is a function returning its value. normally that's just the
+// default, with the `tune` feature they are backed by atomics and exposed as spin options
+macro_rules! tunables {
+    ($($name:ident: $default:literal, $min:literal, $max:literal, $step:literal;)*) => {
+        $(
+            #[cfg(not(feature = "tune"))]
+            #[must_use]
+            #[inline(always)]
+            pub const fn $name() -> i32 {
+                $default
+            }
+
+            #[cfg(feature = "tune")]
+            #[must_use]
+            #[inline(always)]
+            pub fn $name() -> i32 {
+                VALUES[Param::$name as usize].load(std::sync::atomic::Ordering::Relaxed)
+            }
+        )*
+
+        #[cfg(feature = "tune")]
+        #[allow(non_camel_case_types)]
+        enum Param {
+            $($name,)*
+        }
+
+        #[cfg(feature = "tune")]
+        static VALUES: [std::sync::atomic::AtomicI32; TUNABLES.len()] = [
+            $(std::sync::atomic::AtomicI32::new($default),)*
+        ];
+
+        static TUNABLES: &[Tunable] = &[
+            $(Tunable {
+                name: stringify!($name),
+                default: $default,
+                min: $min,
+                max: $max,
+                step: $step,
+            },)*
+        ];
+    };
+}
+
+struct Tunable {
+    name: &'static str,
+    default: i32,
+    min: i32,
+    max: i32,
+    step: i32,
+}
+
+// name: default, min, max, spsa step
+tunables! {
+    // eval, all in centiflats
+    flat_value: 75, 25, 150, 4;
+    flat_in_hand: -13, -40, 10, 2;
+    road_adjacency: 9, -10, 30, 1;
+    road_line: 7, -10, 30, 1;
+    flat_support: 30, 0, 80, 3;
+    flat_captive: -40, -100, 0, 3;
+    wall_support: 35, 0, 80, 3;
+    wall_captive: -15, -60, 20, 2;
+    cap_support: 40, 0, 80, 3;
+    cap_captive: -20, -60, 20, 2;
+    isolated_cap: -50, -120, 0, 4;
+    // capstones by the edge distances of their file and rank (CAP_EDGE_DISTS in eval.rs),
+    // which is symmetric, so cap_psqt_i_j also covers j, i
+    cap_psqt_0_0: -20, -60, 30, 3;
+    cap_psqt_0_1: -5, -40, 40, 3;
+    cap_psqt_0_2: -5, -40, 40, 3;
+    cap_psqt_1_1: 10, -30, 50, 3;
+    cap_psqt_1_2: 18, -30, 60, 3;
+    cap_psqt_2_2: 35, -20, 80, 3;
+    // rings going outwards from the centre on 6x6 (RINGS in eval.rs)
+    ring_0: 2, -30, 30, 2;
+    ring_1: 8, -30, 30, 2;
+    ring_2: -5, -30, 30, 2;
+    ring_3: -15, -50, 20, 2;
+    ring_4: -40, -80, 10, 3;
+    tempo: 30, 0, 60, 2;
+
+    // search
+    aspiration_delta: 25, 5, 60, 2;
+    rfp_max_depth: 6, 2, 10, 1;
+    rfp_margin: 100, 40, 200, 6;
+    rfp_margin_base: 100, 0, 200, 6;
+    rfp_cutnode_bonus: 50, 0, 120, 5;
+    nmp_min_depth: 4, 2, 8, 1;
+    nmp_base_reduction: 3, 1, 6, 1;
+    nmp_depth_divisor: 4, 2, 8, 1;
+    lmp_max_depth: 6, 2, 10, 1;
+    lmp_base: 5, 1, 10, 1;
+    lmp_depth_scale: 2, 1, 4, 1;
+    // in 1/1024ths of a ply
+    lmr_base: 512, 0, 1536, 48;
+    // in hundredths
+    lmr_divisor: 250, 150, 400, 12;
+}
+
+// the `tunables!` entry for `name` with `value` as its default, widening the range if needed
+#[must_use]
+pub fn format_entry(name: &str, value: i32) -> Option<String> {
+    let tunable = TUNABLES.iter().find(|tunable| tunable.name == name)?;
+
+    Some(format!(
+        "{}: {}, {}, {}, {}; // was {}",
+        tunable.name,
+        value,
+        tunable.min.min(value),
+        tunable.max.max(value),
+        tunable.step,
+        tunable.default
+    ))
+}
+
+#[cfg(feature = "tune")]
+pub fn print_options() {
+    for tunable in TUNABLES {
+        println!(
+            "option name {} type spin default {} min {} max {}",
+            tunable.name, tunable.default, tunable.min, tunable.max
+        );
+    }
+}
+
+// openbench format: name, type, value, min, max, c_end, r_end
+#[cfg(feature = "tune")]
+pub fn print_spsa() {
+    for (tunable, value) in TUNABLES.iter().zip(VALUES.iter()) {
+        println!(
+            "{}, int, {}, {}, {}, {}, 0.002",
+            tunable.name,
+            value.load(std::sync::atomic::Ordering::Relaxed),
+            tunable.min,
+            tunable.max,
+            tunable.step
+        );
+    }
+}
+
+// returns false if `name` is not a tunable
+#[cfg(feature = "tune")]
+pub fn set_option(name: &str, value: &str) -> bool {
+    let Some(idx) = TUNABLES
+        .iter()
+        .position(|tunable| tunable.name.eq_ignore_ascii_case(name))
+    else {
+        return false;
+    };
+
+    let tunable = &TUNABLES[idx];
+
+    match value.parse::<i32>() {
+        Ok(value) => VALUES[idx].store(
+            value.clamp(tunable.min, tunable.max),
+            std::sync::atomic::Ordering::Relaxed,
+        ),
+        Err(_) => eprintln!("Invalid value '{}' for {}", value, tunable.name),
+    }
+
+    true
+}
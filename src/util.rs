@@ -21,4 +21,7 @@
  * SOFTWARE.
  */
 
+pub mod bits;
+pub mod checksum;
 pub mod command_channel;
+pub mod le;
@@ -0,0 +1,83 @@
+/*
+ * syntaks, a TEI Tak engine
+ * Copyright (c) 2026 Ciekce
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+// bits are packed least significant first, starting a new byte whenever one fills up
+
+pub struct BitWriter<'a> {
+    dst: &'a mut Vec<u8>,
+    // bits used in the last byte, 0 if a new one has to be started
+    used: u32,
+}
+
+impl<'a> BitWriter<'a> {
+    pub fn new(dst: &'a mut Vec<u8>) -> Self {
+        Self { dst, used: 0 }
+    }
+
+    pub fn write(&mut self, value: u64, bits: u32) {
+        debug_assert!(bits <= u64::BITS);
+        debug_assert!(bits == u64::BITS || value >> bits == 0);
+
+        for idx in 0..bits {
+            if self.used == 0 {
+                self.dst.push(0);
+            }
+
+            *self.dst.last_mut().unwrap() |= (((value >> idx) & 0x1) as u8) << self.used;
+            self.used = (self.used + 1) % u8::BITS;
+        }
+    }
+}
+
+pub struct BitReader<'a> {
+    src: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    pub fn new(src: &'a [u8]) -> Self {
+        Self { src, pos: 0 }
+    }
+
+    // None if there are not enough bits left
+    #[must_use]
+    pub fn read(&mut self, bits: u32) -> Option<u64> {
+        debug_assert!(bits <= u64::BITS);
+
+        let mut value = 0;
+
+        for idx in 0..bits {
+            let byte = *self.src.get(self.pos / 8)?;
+            value |= (((byte >> (self.pos % 8)) & 0x1) as u64) << idx;
+            self.pos += 1;
+        }
+
+        Some(value)
+    }
+
+    // including the partially read last byte
+    #[must_use]
+    pub fn bytes_read(&self) -> usize {
+        self.pos.div_ceil(8)
+    }
+}
@@ -0,0 +1,236 @@
+/*
+ * syntaks, a TEI Tak engine
+ * Copyright (c) 2026 Ciekce
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+// Perft regression suite. Counts above FAST_MAX_NODES are only checked by the deep tier,
+// which is best run in release:
+//     cargo test --release -- --ignored
+//
+// perft does not stop at game-ending positions, so once roads are possible these counts
+// differ from those of engines that do.
+//
+// Only the startpos counts come from another engine. Every other group was
+// generated by this engine and pins its current behaviour, so a change in one of those
+// counts needs checking by hand before the expectation is updated.
+
+use std::thread;
+use syntaks::Position;
+use syntaks::perft::{PerftOptions, perft, perft_unmake, perft_with_options};
+
+const FAST_MAX_NODES: usize = 100_000;
+
+struct PerftCase {
+    tps: &'static str,
+    // indexed by depth - 1
+    counts: &'static [usize],
+}
+
+// the same as tiltak's published startpos counts, apart from 3x3 at depth 6 which
+// includes moves made after a road has already ended the game
+const STARTPOS: &[PerftCase] = &[
+    PerftCase {
+        tps: "x3/x3/x3 1 1",
+        counts: &[9, 72, 1200, 17792, 271812, 3747480],
+    },
+    PerftCase {
+        tps: "x4/x4/x4/x4 1 1",
+        counts: &[16, 240, 7440, 216464, 6468872],
+    },
+    PerftCase {
+        tps: "x5/x5/x5/x5/x5 1 1",
+        counts: &[25, 600, 43320, 2999784, 187855252],
+    },
+    PerftCase {
+        tps: "x6/x6/x6/x6/x6/x6 1 1",
+        counts: &[36, 1260, 132720, 13586048, 1253506520],
+    },
+    PerftCase {
+        tps: "x7/x7/x7/x7/x7/x7/x7 1 1",
+        counts: &[49, 2352, 339696, 48051008],
+    },
+    PerftCase {
+        tps: "x8/x8/x8/x8/x8/x8/x8/x8 1 1",
+        counts: &[64, 4032, 764064, 142512336],
+    },
+];
+
+// self-generated. the first two plies place the opponent's flat
+const OPENING_SWAP: &[PerftCase] = &[
+    PerftCase {
+        tps: "x3/x,2,x/x3 2 1",
+        counts: &[8, 132, 2116, 32220],
+    },
+    PerftCase {
+        tps: "x5/x5/x2,2,x2/x5/x5 2 1",
+        counts: &[24, 1732, 121280, 7591724],
+    },
+    PerftCase {
+        tps: "x6/x6/x6/x6/x6/2,x5 2 1",
+        counts: &[35, 3688, 372708, 34400372],
+    },
+    PerftCase {
+        tps: "x6/x6/x6/x6/x6/2,x4,1 1 2",
+        counts: &[104, 10506, 954906, 84335672],
+    },
+];
+
+// self-generated. stacks at and above the carry limit
+const TALL_STACKS: &[PerftCase] = &[
+    PerftCase {
+        tps: "x6/x6/x6/x6/x6/212121C,x5 1 8",
+        counts: &[194, 20238, 2364376, 219191390],
+    },
+    PerftCase {
+        tps: "x6/x6/x2,2121212121,x3/x6/x6/x6 1 11",
+        counts: &[229, 29072, 5110504, 627981924],
+    },
+    PerftCase {
+        tps: "x5/x,2S,x3/x,1112121C,x,2S,x/x5/x5 1 9",
+        counts: &[74, 5419, 367377, 24465231],
+    },
+    PerftCase {
+        tps: "x8/x8/x8/x3,212121212121,x4/x8/x8/x8/x8 1 13",
+        counts: &[697, 180590, 65802036],
+    },
+];
+
+// self-generated. capstones next to walls, which they can only flatten on their own
+const CAPSTONE_CRUSHES: &[PerftCase] = &[
+    PerftCase {
+        tps: "x6/x6/x2,2S,x3/x,2S,1C,2S,x2/x2,2S,x3/x6 1 6",
+        counts: &[66, 6744, 442098, 40180356],
+    },
+    PerftCase {
+        tps: "x6/x6/x6/121C,x,2S,x3/x6/x6 1 5",
+        counts: &[86, 8891, 729508, 66366974],
+    },
+    PerftCase {
+        tps: "x6/x6/x2,2C,x3/x2,1C,1S,x2/x6/x6 2 4",
+        counts: &[69, 4835, 329864, 22849518],
+    },
+    PerftCase {
+        tps: "x7/x7/x2,2S,x4/x,2S,1C,2S,1C,2S,x/x2,2S,x4/x7/x7 1 8",
+        counts: &[92, 12634, 1156840, 155695230],
+    },
+];
+
+// self-generated. players with one flat left, or only their capstone
+const LOW_RESERVES: &[PerftCase] = &[
+    PerftCase {
+        tps: "11111,1,1,1/2222,2,2121,2/1,1,12,1/2,2222,x,x 1 15",
+        counts: &[78, 5628, 413014, 29189815],
+    },
+    PerftCase {
+        tps: "1111111,222222222,1,2,1/22222,1111,2,1,x/1,2,1,2,1/2,1,2C,1,x/1,2,1,x,x 1 22",
+        counts: &[128, 15156, 1922707, 248110819],
+    },
+    PerftCase {
+        tps: "22222222222,1,2,1,2,1/2,111111,2,1,2,1/222222,2,1,2,x,1/1,2,1C,2,1,x/2,x,2,1,2,x/x,1,x2,1,2 2 25",
+        counts: &[298, 52470, 13978562, 1843272494],
+    },
+];
+
+const ALL: &[&[PerftCase]] = &[STARTPOS, OPENING_SWAP, TALL_STACKS, CAPSTONE_CRUSHES, LOW_RESERVES];
+
+fn check(cases: &[PerftCase], max_nodes: usize, perft: impl Fn(&Position, i32) -> usize) {
+    for case in cases {
+        let pos: Position = case
+            .tps
+            .parse()
+            .unwrap_or_else(|err| panic!("invalid TPS '{}': {:?}", case.tps, err));
+
+        for (depth, &expected) in (1..).zip(case.counts) {
+            if expected > max_nodes {
+                break;
+            }
+
+            assert_eq!(perft(&pos, depth), expected, "perft {} of '{}'", depth, case.tps);
+        }
+    }
+}
+
+fn check_fast(cases: &[PerftCase]) {
+    check(cases, FAST_MAX_NODES, perft);
+    check(cases, FAST_MAX_NODES, perft_unmake);
+}
+
+#[test]
+fn startpos() {
+    check_fast(STARTPOS);
+}
+
+#[test]
+fn opening_swap() {
+    check_fast(OPENING_SWAP);
+}
+
+#[test]
+fn tall_stacks() {
+    check_fast(TALL_STACKS);
+}
+
+#[test]
+fn capstone_crushes() {
+    check_fast(CAPSTONE_CRUSHES);
+}
+
+#[test]
+fn low_reserves() {
+    check_fast(LOW_RESERVES);
+}
+
+#[test]
+fn depth_zero() {
+    let pos: Position = STARTPOS[3].tps.parse().unwrap();
+
+    assert_eq!(perft(&pos, 0), 1);
+    assert_eq!(perft_unmake(&pos, 0), 1);
+    assert_eq!(perft_with_options(&pos, 0, &PerftOptions::default()), 1);
+}
+
+#[test]
+fn threaded_and_hashed() {
+    let options = PerftOptions {
+        threads: 2,
+        hash_mib: 1,
+        stats: false,
+    };
+
+    for cases in ALL {
+        check(cases, FAST_MAX_NODES, |pos, depth| {
+            perft_with_options(pos, depth, &options)
+        });
+    }
+}
+
+#[test]
+#[ignore = "slow, run in release with --ignored"]
+fn deep() {
+    let options = PerftOptions {
+        threads: thread::available_parallelism().map_or(1, |threads| threads.get()),
+        ..Default::default()
+    };
+
+    for cases in ALL {
+        check(cases, usize::MAX, |pos, depth| perft_with_options(pos, depth, &options));
+    }
+}